@@ -4,7 +4,7 @@ extern crate cpal;
 use aiff::reader::AiffReader;
 use cpal::{
     traits::{DeviceTrait, HostTrait, StreamTrait},
-    Sample, SampleRate,
+    SampleRate,
 };
 use std::fs::File;
 
@@ -62,10 +62,7 @@ fn main() {
 
     println!("stream config {:?}", stream_config);
 
-    let samples: Vec<_> =
-        reader.samples::<i32>().iter().map(|s| s.to_f32()).collect();
-
-    // let samples: Vec<_> = reader.samples().iter().map(|s| s.to_f32()).collect();
+    let samples = reader.samples_f32().unwrap();
     let mut idx = 0;
 
     let duration = comm.num_sample_frames as f64 / comm.sample_rate;
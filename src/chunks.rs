@@ -1,27 +1,25 @@
-use super::extended::parse_extended_precision_bytes;
+use super::extended::{
+    parse_extended_precision_bytes, parse_extended_precision_exact,
+    ExtendedError,
+};
 use super::{
+    codecs,
+    error::Error,
     ids::{self, ChunkID},
     reader::{self, Buffer},
 };
 use id3;
-use std::io::{Read, Seek, SeekFrom};
-use std::ops::Div;
-
-#[derive(Debug)]
-pub enum ChunkError {
-    InvalidID(ChunkID),
-    InvalidFormType(ChunkID),
-    InvalidID3Version([u8; 2]),
-    InvalidSize(i32, i32),     // expected, got,
-    InvalidData(&'static str), // failed to parse something
-}
+use log::{debug, trace};
+use std::convert::TryInto;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::time::Duration;
 
 // TODO rename 'build'
 pub trait Chunk {
     fn parse(
         buffer: Buffer<impl Read + Seek>,
         id: ChunkID,
-    ) -> Result<Self, ChunkError>
+    ) -> Result<Self, Error>
     where
         Self: Sized;
 }
@@ -33,6 +31,7 @@ pub trait Chunk {
 #[derive(Debug)]
 pub struct FormChunk {
     size: i32,                     // required
+    form_type: ChunkID,            // AIFF or AIFC
     common: Option<CommonChunk>,   // required
     sound: Option<SoundDataChunk>, // required if num_sample_frames > 0
     comments: Option<CommentsChunk>,
@@ -42,9 +41,32 @@ pub struct FormChunk {
     markers: Option<Vec<MarkerChunk>>,
     midi: Option<Vec<MIDIDataChunk>>,
     apps: Option<Vec<ApplicationSpecificChunk>>,
+    // AIFC only, where it is required
+    format_version: Option<FormatVersionChunk>,
+    raw_chunks: Option<Vec<RawChunk>>, // unknown to the parser, in file order
 }
 
 impl FormChunk {
+    pub fn size(&self) -> i32 {
+        self.size
+    }
+
+    pub fn form_type(&self) -> &ChunkID {
+        &self.form_type
+    }
+
+    pub fn is_aiff_c(&self) -> bool {
+        &self.form_type == ids::AIFF_C
+    }
+
+    pub fn format_version(&self) -> &Option<FormatVersionChunk> {
+        &self.format_version
+    }
+
+    pub fn set_format_version(&mut self, c: FormatVersionChunk) {
+        self.format_version = Some(c);
+    }
+
     pub fn common(&self) -> &Option<CommonChunk> {
         &self.common
     }
@@ -117,32 +139,52 @@ impl FormChunk {
         }
     }
 
-    pub fn duration(&self) -> Option<f64> {
-        if let Some(common) = &self.common {
-            Some((common.num_sample_frames as f64).div(common.sample_rate))
+    pub fn raw_chunks(&self) -> &Option<Vec<RawChunk>> {
+        &self.raw_chunks
+    }
+
+    pub fn add_raw_chunk(&mut self, c: RawChunk) {
+        if self.raw_chunks.is_none() {
+            self.raw_chunks = Some(vec![]);
+        }
+        if let Some(r) = &mut self.raw_chunks {
+            r.push(c);
         } else {
-            None
+            panic!("vec should exist at this point")
         }
     }
+
+    // in seconds, computed from the exact sample rate so it doesn't drift
+    // for long files. None without a COMM chunk or with a zero sample rate
+    pub fn duration(&self) -> Option<f64> {
+        let common = self.common.as_ref()?;
+        let rate = common.exact_sample_rate;
+        Some(rate.frames_to_duration(common.frame_count())?.as_secs_f64())
+    }
 }
 
 impl Chunk for FormChunk {
     fn parse(
         buf: Buffer<impl Read + Seek>,
         id: ChunkID,
-    ) -> Result<FormChunk, ChunkError> {
+    ) -> Result<FormChunk, Error> {
         if &id != ids::FORM {
-            return Err(ChunkError::InvalidID(id));
+            return Err(Error::InvalidID(id));
         }
 
-        let size = reader::read_i32_be(buf);
-        println!("form chunk bytes {}", size);
+        let size = reader::read_i32_be(buf)?;
         let mut form_type = [0; 4];
-        buf.read_exact(&mut form_type).unwrap();
+        buf.read_exact(&mut form_type)?;
+        debug!(
+            "{:?} form, {} bytes",
+            String::from_utf8_lossy(&form_type),
+            size
+        );
 
         match &form_type {
-            ids::AIFF => Ok(FormChunk {
+            ids::AIFF | ids::AIFF_C => Ok(FormChunk {
                 size,
+                form_type,
                 common: None,
                 sound: None,
                 comments: None,
@@ -152,16 +194,48 @@ impl Chunk for FormChunk {
                 markers: None,
                 midi: None,
                 apps: None,
+                format_version: None,
+                raw_chunks: None,
             }),
-            ids::AIFF_C => {
-                println!("aiff c file detected; unsupported");
-                Err(ChunkError::InvalidFormType(form_type))
-            }
-            &x => Err(ChunkError::InvalidFormType(x)),
+            &x => Err(Error::InvalidFormType(x)),
+        }
+    }
+}
+
+// AIFC only - timestamp of the AIFC spec revision the file conforms to
+pub const AIFC_VERSION_1: u32 = 0xA2805140;
+
+#[derive(Debug)]
+pub struct FormatVersionChunk {
+    pub size: i32,
+    pub timestamp: u32,
+}
+
+impl Chunk for FormatVersionChunk {
+    fn parse(
+        buf: Buffer<impl Read + Seek>,
+        id: ChunkID,
+    ) -> Result<FormatVersionChunk, Error> {
+        if &id != ids::FVER {
+            return Err(Error::InvalidID(id));
+        }
+
+        let size = reader::read_i32_be(buf)?;
+        if size != 4 {
+            return Err(Error::InvalidSize(4, size));
         }
+        let timestamp = reader::read_u32_be(buf)?;
+
+        Ok(FormatVersionChunk { size, timestamp })
     }
 }
 
+// size of the COMM chunk in a plain AIFF file. AIFC adds the compression
+// type and name
+pub const AIFF_COMMON_SIZE: i32 = 18;
+// the smallest AIFC COMM chunk that holds a compression type
+pub const AIFC_COMMON_MIN_SIZE: i32 = AIFF_COMMON_SIZE + 4;
+
 #[derive(Debug)]
 pub struct CommonChunk {
     pub size: i32,
@@ -169,33 +243,61 @@ pub struct CommonChunk {
     pub num_sample_frames: u32,
     pub bit_rate: i16, // in the spec, this is defined as `sample_size`
     pub sample_rate: f64, // 80 bit extended floating pt num
+    pub exact_sample_rate: SampleRate,
+    // AIFC only. plain AIFF is treated as NONE / "not compressed"
+    pub compression_type: ChunkID,
+    pub compression_name: String,
 }
 
-impl Chunk for CommonChunk {
-    fn parse(
+impl CommonChunk {
+    pub fn is_compressed(&self) -> bool {
+        &self.compression_type != ids::NONE
+    }
+
+    // the number of sample frames once decoded. for packet based compression
+    // types, num_sample_frames is the number of packets
+    pub fn frame_count(&self) -> u64 {
+        self.num_sample_frames as u64
+            * codecs::frames_per_packet(&self.compression_type) as u64
+    }
+
+    // not a `Chunk`, as the layout depends on the form type. an AIFC COMM
+    // chunk too small for the compression type is read as NONE
+    pub fn parse(
         buf: Buffer<impl Read + Seek>,
         id: ChunkID,
-    ) -> Result<CommonChunk, ChunkError> {
+        form_type: &ChunkID,
+    ) -> Result<CommonChunk, Error> {
         if &id != ids::COMMON {
-            return Err(ChunkError::InvalidID(id));
+            return Err(Error::InvalidID(id));
         }
 
         let (size, num_channels, num_sample_frames, bit_rate) = (
-            reader::read_i32_be(buf),
-            reader::read_i16_be(buf),
-            reader::read_u32_be(buf),
-            reader::read_i16_be(buf),
+            reader::read_i32_be(buf)?,
+            reader::read_i16_be(buf)?,
+            reader::read_u32_be(buf)?,
+            reader::read_i16_be(buf)?,
         );
 
         let mut rate_buf = [0; 10]; // 1 bit sign, 15 bits exponent
-        buf.read_exact(&mut rate_buf).unwrap();
-
-        let sample_rate = match parse_extended_precision_bytes(rate_buf) {
-            Ok(s) => s,
-            Err(()) => {
-                return Err(ChunkError::InvalidData("Extended Precision"))
-            }
-        };
+        buf.read_exact(&mut rate_buf)?;
+
+        let sample_rate = parse_extended_precision_bytes(rate_buf)
+            .map_err(Error::SampleRate)?;
+        let exact_sample_rate =
+            SampleRate::from_extended(rate_buf).map_err(Error::SampleRate)?;
+
+        let (compression_type, compression_name) =
+            if form_type == ids::AIFF_C && size >= AIFC_COMMON_MIN_SIZE {
+                let compression_type = reader::read_chunk_id(buf)?;
+                let compression_name = match size > AIFC_COMMON_MIN_SIZE {
+                    true => reader::read_pstring(buf)?,
+                    false => String::new(),
+                };
+                (compression_type, compression_name)
+            } else {
+                (*ids::NONE, String::from("not compressed"))
+            };
 
         Ok(CommonChunk {
             size,
@@ -203,10 +305,82 @@ impl Chunk for CommonChunk {
             num_sample_frames,
             bit_rate,
             sample_rate,
+            exact_sample_rate,
+            compression_type,
+            compression_name,
+        })
+    }
+}
+
+// the sample rate exactly as stored, mantissa * 2^exponent. extended
+// precision numbers are a 64 bit integer scaled by a power of 2, so this
+// keeps pull down rates like 44056.0439... that an f64 rounds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SampleRate {
+    pub mantissa: u64, // odd, or 0
+    pub exponent: i32,
+}
+
+impl SampleRate {
+    pub fn from_extended(b: [u8; 10]) -> Result<SampleRate, ExtendedError> {
+        let (is_neg, mantissa, exponent) = parse_extended_precision_exact(b)?;
+        if mantissa == 0 {
+            return Ok(SampleRate {
+                mantissa,
+                exponent: 0,
+            });
+        }
+        if is_neg {
+            return Err(ExtendedError::Negative);
+        }
+        let zeros = mantissa.trailing_zeros();
+        Ok(SampleRate {
+            mantissa: mantissa >> zeros,
+            exponent: exponent + zeros as i32,
         })
     }
+
+    // the rate as numerator / denominator, or None if either doesn't fit in
+    // a u128, which only happens for rates below 2^-64 or above 2^128 Hz
+    pub fn fraction(&self) -> Option<(u128, u128)> {
+        let scale = 1u128.checked_shl(self.exponent.unsigned_abs())?;
+        if self.exponent >= 0 {
+            Some(((self.mantissa as u128).checked_mul(scale)?, 1))
+        } else {
+            Some((self.mantissa as u128, scale))
+        }
+    }
+
+    // how long `frames` take to play, rounded up to the nanosecond so that
+    // duration_to_frames turns it back into `frames`. None for a zero rate
+    // or if the math overflows
+    pub fn frames_to_duration(&self, frames: u64) -> Option<Duration> {
+        let (num, den) = self.fraction()?;
+        if num == 0 {
+            return None;
+        }
+        let total = (frames as u128).checked_mul(den)?;
+        let nanos = (total % num).checked_mul(NANOS_PER_SEC)?;
+        let duration =
+            Duration::new((total / num).try_into().ok()?, (nanos / num) as u32);
+        match nanos % num {
+            0 => Some(duration),
+            _ => duration.checked_add(Duration::from_nanos(1)),
+        }
+    }
+
+    // the frame playing at `time`, which is the number of whole frames
+    // before it. None if the math overflows
+    pub fn duration_to_frames(&self, time: Duration) -> Option<u64> {
+        let (num, den) = self.fraction()?;
+        let frames = time.as_nanos().checked_mul(num)?
+            / den.checked_mul(NANOS_PER_SEC)?;
+        frames.try_into().ok()
+    }
 }
 
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
 #[derive(Debug)]
 pub struct SoundDataChunk {
     pub size: i32,
@@ -219,28 +393,51 @@ impl Chunk for SoundDataChunk {
     fn parse(
         buf: Buffer<impl Read + Seek>,
         id: ChunkID,
-    ) -> Result<SoundDataChunk, ChunkError> {
-        if &id != ids::SOUND {
-            return Err(ChunkError::InvalidID(id));
-        }
+    ) -> Result<SoundDataChunk, Error> {
+        let mut chunk = SoundDataChunk::parse_header(buf, id)?;
 
-        let size = reader::read_i32_be(buf);
-        let offset = reader::read_u32_be(buf);
-        let block_size = reader::read_u32_be(buf);
+        chunk.sound_data =
+            reader::read_bytes(buf, chunk.sound_size() as usize)?;
 
-        // TODO some sort of streaming read optimization?
-        let sound_size = size - 8; // account for offset + block size bytes
-        let mut sound_data = vec![0u8; sound_size as usize];
+        Ok(chunk)
+    }
+}
 
-        buf.read_exact(&mut sound_data).unwrap();
+impl SoundDataChunk {
+    // reads up to the sound data, leaving `sound_data` empty and the buffer
+    // positioned at the start of it
+    pub fn parse_header(
+        buf: Buffer<impl Read + Seek>,
+        id: ChunkID,
+    ) -> Result<SoundDataChunk, Error> {
+        if &id != ids::SOUND {
+            return Err(Error::InvalidID(id));
+        }
+
+        let size = reader::read_i32_be(buf)?;
+        let offset = reader::read_u32_be(buf)?;
+        let block_size = reader::read_u32_be(buf)?;
 
         Ok(SoundDataChunk {
             size,
             offset,
             block_size,
-            sound_data,
+            sound_data: vec![],
         })
     }
+
+    // bytes of sound data, including the offset padding
+    pub fn sound_size(&self) -> u32 {
+        (self.size - 8).max(0) as u32 // account for offset + block size bytes
+    }
+
+    // the sound data from the first sample frame on. offset skips any
+    // padding used to block align it, block_size only describes that
+    // alignment and doesn't change how frames are laid out
+    pub fn sample_data(&self) -> &[u8] {
+        let offset = (self.offset as usize).min(self.sound_data.len());
+        &self.sound_data[offset..]
+    }
 }
 
 type MarkerId = i16;
@@ -252,17 +449,16 @@ pub struct Marker {
 }
 
 impl Marker {
-    // TODO return result
-    pub fn from_reader<R: Read + Seek>(r: &mut R) -> Marker {
-        let id = reader::read_i16_be(r);
-        let position = reader::read_u32_be(r);
-        let marker_name = reader::read_pstring(r);
+    pub fn from_reader<R: Read + Seek>(r: &mut R) -> io::Result<Marker> {
+        let id = reader::read_i16_be(r)?;
+        let position = reader::read_u32_be(r)?;
+        let marker_name = reader::read_pstring(r)?;
 
-        Marker {
+        Ok(Marker {
             id,
             position,
             marker_name,
-        }
+        })
     }
 }
 
@@ -277,18 +473,18 @@ impl Chunk for MarkerChunk {
     fn parse(
         buf: Buffer<impl Read + Seek>,
         id: ChunkID,
-    ) -> Result<MarkerChunk, ChunkError> {
+    ) -> Result<MarkerChunk, Error> {
         if &id != ids::MARKER {
-            return Err(ChunkError::InvalidID(id));
+            return Err(Error::InvalidID(id));
         }
 
-        let size = reader::read_i32_be(buf);
-        let num_markers = reader::read_u16_be(buf);
+        let size = reader::read_i32_be(buf)?;
+        let num_markers = reader::read_u16_be(buf)?;
         let mut markers = Vec::with_capacity(num_markers as usize);
         // is it worth it to read all markers at once ant create from buf?
         // or does the usage of BufReader make it irrelevant?
         for _ in 0..num_markers {
-            markers.push(Marker::from_reader(buf));
+            markers.push(Marker::from_reader(buf)?);
         }
 
         Ok(MarkerChunk {
@@ -318,23 +514,26 @@ impl Chunk for TextChunk {
     fn parse(
         buf: Buffer<impl Read + Seek>,
         id: ChunkID,
-    ) -> Result<TextChunk, ChunkError> {
+    ) -> Result<TextChunk, Error> {
         let chunk_type = match &id {
             ids::NAME => TextChunkType::Name,
             ids::AUTHOR => TextChunkType::Author,
             ids::COPYRIGHT => TextChunkType::Copyright,
             ids::ANNOTATION => TextChunkType::Annotation,
-            _ => return Err(ChunkError::InvalidID(id)),
+            _ => return Err(Error::InvalidID(id)),
         };
 
-        let size = reader::read_i32_be(buf);
-        let mut text_bytes = vec![0; size as usize];
-        buf.read_exact(&mut text_bytes).unwrap();
-        let text = String::from_utf8(text_bytes).unwrap();
+        let size = reader::read_i32_be(buf)?;
+        if size < 0 {
+            return Err(Error::InvalidData("chunk size"));
+        }
+        let text_bytes = reader::read_bytes(buf, size as usize)?;
+        // not necessarily utf8, eg mac roman
+        let text = String::from_utf8_lossy(&text_bytes).into_owned();
 
         if size % 2 > 0 {
             // if odd, pad byte present - skip it
-            buf.seek(SeekFrom::Current(1)).unwrap();
+            buf.seek(SeekFrom::Current(1))?;
         }
 
         Ok(TextChunk {
@@ -354,17 +553,16 @@ pub struct Loop {
 }
 
 impl Loop {
-    // TODO return result
-    pub fn from_reader(r: &mut impl Read) -> Loop {
-        let play_mode = reader::read_i16_be(r);
-        let begin_loop = reader::read_i16_be(r);
-        let end_loop = reader::read_i16_be(r);
+    pub fn from_reader(r: &mut impl Read) -> io::Result<Loop> {
+        let play_mode = reader::read_i16_be(r)?;
+        let begin_loop = reader::read_i16_be(r)?;
+        let end_loop = reader::read_i16_be(r)?;
 
-        Loop {
+        Ok(Loop {
             play_mode,
             begin_loop,
             end_loop,
-        }
+        })
     }
 }
 
@@ -387,22 +585,22 @@ impl Chunk for InstrumentChunk {
     fn parse(
         buf: Buffer<impl Read + Seek>,
         id: ChunkID,
-    ) -> Result<InstrumentChunk, ChunkError> {
+    ) -> Result<InstrumentChunk, Error> {
         if &id != ids::INSTRUMENT {
-            return Err(ChunkError::InvalidID(id));
+            return Err(Error::InvalidID(id));
         }
 
-        let size = reader::read_i32_be(buf);
-        let base_note = reader::read_i8_be(buf);
-        let detune = reader::read_i8_be(buf);
-        let low_note = reader::read_i8_be(buf);
-        let high_note = reader::read_i8_be(buf);
-        let low_velocity = reader::read_i8_be(buf);
-        let high_velocity = reader::read_i8_be(buf);
-        let gain = reader::read_i16_be(buf);
+        let size = reader::read_i32_be(buf)?;
+        let base_note = reader::read_i8_be(buf)?;
+        let detune = reader::read_i8_be(buf)?;
+        let low_note = reader::read_i8_be(buf)?;
+        let high_note = reader::read_i8_be(buf)?;
+        let low_velocity = reader::read_i8_be(buf)?;
+        let high_velocity = reader::read_i8_be(buf)?;
+        let gain = reader::read_i16_be(buf)?;
 
-        let sustain_loop = Loop::from_reader(buf);
-        let release_loop = Loop::from_reader(buf);
+        let sustain_loop = Loop::from_reader(buf)?;
+        let release_loop = Loop::from_reader(buf)?;
 
         Ok(InstrumentChunk {
             size,
@@ -429,15 +627,17 @@ impl Chunk for MIDIDataChunk {
     fn parse(
         buf: Buffer<impl Read + Seek>,
         id: ChunkID,
-    ) -> Result<MIDIDataChunk, ChunkError> {
+    ) -> Result<MIDIDataChunk, Error> {
         if &id != ids::MIDI {
-            return Err(ChunkError::InvalidID(id));
+            return Err(Error::InvalidID(id));
         }
 
-        let size = reader::read_i32_be(buf);
+        let size = reader::read_i32_be(buf)?;
+        if size < 0 {
+            return Err(Error::InvalidData("chunk size"));
+        }
 
-        let mut data = vec![0; size as usize];
-        buf.read_exact(&mut data).unwrap();
+        let data = reader::read_bytes(buf, size as usize)?;
 
         Ok(MIDIDataChunk { size, data })
     }
@@ -455,18 +655,18 @@ impl Chunk for AudioRecordingChunk {
     fn parse(
         buf: Buffer<impl Read + Seek>,
         id: ChunkID,
-    ) -> Result<AudioRecordingChunk, ChunkError> {
+    ) -> Result<AudioRecordingChunk, Error> {
         if &id != ids::RECORDING {
-            return Err(ChunkError::InvalidID(id));
+            return Err(Error::InvalidID(id));
         }
 
-        let size = reader::read_i32_be(buf);
+        let size = reader::read_i32_be(buf)?;
         if size != 24 {
-            return Err(ChunkError::InvalidSize(24, size));
+            return Err(Error::InvalidSize(24, size));
         }
 
         let mut data = [0; 24];
-        buf.read_exact(&mut data).unwrap();
+        buf.read_exact(&mut data)?;
 
         Ok(AudioRecordingChunk { size, data })
     }
@@ -483,15 +683,18 @@ impl Chunk for ApplicationSpecificChunk {
     fn parse(
         buf: Buffer<impl Read + Seek>,
         id: ChunkID,
-    ) -> Result<ApplicationSpecificChunk, ChunkError> {
+    ) -> Result<ApplicationSpecificChunk, Error> {
         if &id != ids::APPLICATION {
-            return Err(ChunkError::InvalidID(id));
+            return Err(Error::InvalidID(id));
         }
 
-        let size = reader::read_i32_be(buf);
-        let application_signature = reader::read_chunk_id(buf); // TODO verify
-        let mut data = vec![0; (size - 4) as usize]; // account for sig size
-        buf.read_exact(&mut data).unwrap();
+        let size = reader::read_i32_be(buf)?;
+        if size < 4 {
+            return Err(Error::InvalidData("chunk size"));
+        }
+        let application_signature = reader::read_chunk_id(buf)?; // TODO verify
+                                                                 // account for sig size
+        let data = reader::read_bytes(buf, (size - 4) as usize)?;
 
         Ok(ApplicationSpecificChunk {
             size,
@@ -510,22 +713,21 @@ pub struct Comment {
 }
 
 impl Comment {
-    // TODO return result
-    pub fn from_reader(r: &mut impl Read) -> Comment {
-        let timestamp = reader::read_u32_be(r);
-        let marker_id = reader::read_i16_be(r);
-        let count = reader::read_u16_be(r);
+    pub fn from_reader(r: &mut impl Read) -> io::Result<Comment> {
+        let timestamp = reader::read_u32_be(r)?;
+        let marker_id = reader::read_i16_be(r)?;
+        let count = reader::read_u16_be(r)?;
 
         let mut str_buf = vec![0; count as usize];
-        r.read_exact(&mut str_buf).unwrap();
-        let text = String::from_utf8(str_buf).unwrap();
+        r.read_exact(&mut str_buf)?;
+        let text = String::from_utf8_lossy(&str_buf).into_owned();
 
-        Comment {
+        Ok(Comment {
             timestamp,
             marker_id,
             count,
             text,
-        }
+        })
     }
 }
 
@@ -540,17 +742,17 @@ impl Chunk for CommentsChunk {
     fn parse(
         buf: Buffer<impl Read + Seek>,
         id: ChunkID,
-    ) -> Result<CommentsChunk, ChunkError> {
+    ) -> Result<CommentsChunk, Error> {
         if &id != ids::COMMENTS {
-            return Err(ChunkError::InvalidID(id));
+            return Err(Error::InvalidID(id));
         }
 
-        let size = reader::read_i32_be(buf);
-        let num_comments = reader::read_u16_be(buf);
+        let size = reader::read_i32_be(buf)?;
+        let num_comments = reader::read_u16_be(buf)?;
 
         let mut comments = Vec::with_capacity(num_comments as usize);
         for _ in 0..num_comments {
-            comments.push(Comment::from_reader(buf))
+            comments.push(Comment::from_reader(buf)?)
         }
 
         Ok(CommentsChunk {
@@ -568,7 +770,7 @@ impl Chunk for CommentsChunk {
 //     fn parse(
 //         buf: Buffer<impl Read + Seek>,
 //         id: ChunkID,
-//     ) -> Result<ID3v1Chunk, ChunkError> {
+//     ) -> Result<ID3v1Chunk, Error> {
 //     }
 // }
 
@@ -579,31 +781,80 @@ pub struct ID3v2Chunk {
 }
 
 // should this be an optional feature? maybe consumer already has id3 parsing
+// an "ID3 " chunk, holding a whole ID3v2 tag
 impl Chunk for ID3v2Chunk {
     fn parse(
         buf: Buffer<impl Read + Seek>,
         id: ChunkID,
-    ) -> Result<ID3v2Chunk, ChunkError> {
-        if &id[0..3] != ids::ID3 && &id[1..] != ids::ID3 {
-            return Err(ChunkError::InvalidID(id));
+    ) -> Result<ID3v2Chunk, Error> {
+        if &id != ids::ID3_CHUNK && &id != ids::ID3_CHUNK_LOWER {
+            return Err(Error::InvalidID(id));
+        }
+
+        let size = reader::read_i32_be(buf)?;
+        if size < 10 {
+            return Err(Error::InvalidData("chunk size"));
         }
+        ID3v2Chunk::from_reader(buf)
+    }
+}
 
+impl ID3v2Chunk {
+    // a tag on its own, starting with its "ID3" header
+    pub fn from_reader(
+        buf: Buffer<impl Read + Seek>,
+    ) -> Result<ID3v2Chunk, Error> {
         // TODO is this necessary? can we get this from id3 read
-        let mut version = [0; 2];
-        buf.seek(SeekFrom::Current(3)).unwrap();
-        buf.read_exact(&mut version).unwrap();
-        buf.seek(SeekFrom::Current(-5)).unwrap();
+        let mut header = [0; 5];
+        buf.read_exact(&mut header)?;
+        buf.seek(SeekFrom::Current(-5))?;
+        if &header[..3] != ids::ID3 {
+            return Err(Error::InvalidData("ID3 header"));
+        }
+        let version = [header[3], header[4]];
 
         // major versions up to 2.4, no minor versions known
         if version[0] > 4 || version[1] != 0 {
-            return Err(ChunkError::InvalidID3Version(version));
+            return Err(Error::InvalidID3Version(version));
         }
 
         // buffer MUST start with "ID3" or this call will fail
-        let tag = id3::Tag::read_from(buf).unwrap();
-        let frames: Vec<_> = tag.frames().collect();
-        // println!("id3 frames {:?}", frames);
+        let tag = id3::Tag::read_from(buf)?;
+        trace!("ID3 frames {:?}", tag.frames().collect::<Vec<_>>());
 
         Ok(ID3v2Chunk { version })
     }
 }
+
+// a chunk the parser doesn't know, kept so it can be inspected or written
+// back out
+#[derive(Debug)]
+pub struct RawChunk {
+    pub id: ChunkID,
+    pub offset: u64, // of the chunk id in the source
+    pub size: i32,
+    // None for lazy and metadata only readers, which leave it in the source
+    // at offset + 8
+    pub data: Option<Vec<u8>>,
+}
+
+impl RawChunk {
+    // reads the size, leaving the buffer positioned at the start of the data
+    pub fn parse_header(
+        buf: Buffer<impl Read + Seek>,
+        id: ChunkID,
+        offset: u64,
+    ) -> Result<RawChunk, Error> {
+        let size = reader::read_i32_be(buf)?;
+        if size < 0 {
+            return Err(Error::InvalidData("chunk size"));
+        }
+
+        Ok(RawChunk {
+            id,
+            offset,
+            size,
+            data: None,
+        })
+    }
+}
@@ -0,0 +1,132 @@
+// ITU-T G.711 μ-law ('ulaw') and A-law ('alaw') companding
+// based on the public domain Sun Microsystems reference implementation
+
+const SIGN_BIT: u8 = 0x80;
+const QUANT_MASK: u8 = 0x0f;
+const SEG_SHIFT: u8 = 4;
+const SEG_MASK: u8 = 0x70;
+
+const ULAW_BIAS: i16 = 0x84;
+const ULAW_CLIP: i16 = 8159; // 14 bit magnitude, less the bias
+
+// upper bound of each segment, for 14 bit (ulaw) and 13 bit (alaw) input
+const ULAW_SEG_END: [i16; 8] =
+    [0x3f, 0x7f, 0xff, 0x1ff, 0x3ff, 0x7ff, 0xfff, 0x1fff];
+const ALAW_SEG_END: [i16; 8] =
+    [0x1f, 0x3f, 0x7f, 0xff, 0x1ff, 0x3ff, 0x7ff, 0xfff];
+
+fn segment(val: i16, seg_end: &[i16; 8]) -> u8 {
+    seg_end.iter().position(|end| val <= *end).unwrap_or(8) as u8
+}
+
+pub fn ulaw_to_linear(u: u8) -> i16 {
+    let u = !u;
+    let mut t = (((u & QUANT_MASK) as i16) << 3) + ULAW_BIAS;
+    t <<= (u & SEG_MASK) >> SEG_SHIFT;
+
+    if u & SIGN_BIT > 0 {
+        ULAW_BIAS - t
+    } else {
+        t - ULAW_BIAS
+    }
+}
+
+pub fn linear_to_ulaw(sample: i16) -> u8 {
+    let mut val = sample >> 2;
+    let mask = if val < 0 {
+        val = -val;
+        0x7f
+    } else {
+        0xff
+    };
+    val = val.min(ULAW_CLIP) + (ULAW_BIAS >> 2);
+
+    let seg = segment(val, &ULAW_SEG_END);
+    if seg >= 8 {
+        return 0x7f ^ mask;
+    }
+    let uval = (seg << SEG_SHIFT) | ((val >> (seg + 1)) as u8 & QUANT_MASK);
+    uval ^ mask
+}
+
+pub fn alaw_to_linear(a: u8) -> i16 {
+    let a = a ^ 0x55;
+    let mut t = ((a & QUANT_MASK) as i16) << 4;
+    let seg = (a & SEG_MASK) >> SEG_SHIFT;
+    match seg {
+        0 => t += 8,
+        1 => t += 0x108,
+        _ => {
+            t += 0x108;
+            t <<= seg - 1;
+        }
+    }
+
+    if a & SIGN_BIT > 0 {
+        t
+    } else {
+        -t
+    }
+}
+
+pub fn linear_to_alaw(sample: i16) -> u8 {
+    let mut val = sample >> 3;
+    let mask = if val >= 0 {
+        0xd5
+    } else {
+        val = -val - 1;
+        0x55
+    };
+
+    let seg = segment(val, &ALAW_SEG_END);
+    if seg >= 8 {
+        return 0x7f ^ mask;
+    }
+    let shift = if seg < 2 { 1 } else { seg };
+    let aval = (seg << SEG_SHIFT) | ((val >> shift) as u8 & QUANT_MASK);
+    aval ^ mask
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_ulaw() {
+        assert_eq!(ulaw_to_linear(0x00), -32124);
+        assert_eq!(ulaw_to_linear(0x80), 32124);
+        for u in 0..=u8::MAX {
+            let linear = ulaw_to_linear(u);
+            // 0x7f is negative zero, which encodes as 0xff
+            let expected = if u == 0x7f { 0xff } else { u };
+            assert_eq!(linear_to_ulaw(linear), expected);
+        }
+    }
+
+    #[test]
+    fn round_trips_alaw() {
+        assert_eq!(alaw_to_linear(0xd5), 8);
+        assert_eq!(alaw_to_linear(0x55), -8);
+        assert_eq!(alaw_to_linear(0xaa), 32256);
+        assert_eq!(alaw_to_linear(0x2a), -32256);
+        for a in 0..=u8::MAX {
+            assert_eq!(linear_to_alaw(alaw_to_linear(a)), a);
+        }
+    }
+
+    #[test]
+    fn quantizes_within_half_a_step() {
+        // steps double with each segment, so the error is relative to the
+        // magnitude. anything past the largest level is clipped to it
+        let within = |decoded: i16, sample: i16, max: i32| {
+            let sample = (sample as i32).clamp(-max, max);
+            (decoded as i32 - sample).abs() <= sample.abs() / 32 + 8
+        };
+        for sample in i16::MIN..=i16::MAX {
+            let ulaw = ulaw_to_linear(linear_to_ulaw(sample));
+            let alaw = alaw_to_linear(linear_to_alaw(sample));
+            assert!(within(ulaw, sample, 32124));
+            assert!(within(alaw, sample, 32256));
+        }
+    }
+}
@@ -0,0 +1,391 @@
+// GSM 06.10 full rate ('GSM ') decoding, following the reference
+// implementation by Jutta Degener and Carsten Bormann. each 33 byte packet
+// is a big endian bitstream holding a 4 bit signature and 160 mono samples
+// worth of parameters. num_sample_frames counts packets
+
+pub const PACKET_SIZE: usize = 33;
+pub const FRAMES_PER_PACKET: usize = 160;
+
+const SUBFRAME_SIZE: usize = 40;
+const LAR_BITS: [u32; 8] = [6, 6, 5, 5, 4, 4, 3, 3];
+
+// quantized long term prediction gains
+const QLB: [i16; 4] = [3277, 11469, 21299, 32767];
+// normalized mantissas for the RPE inverse quantization
+const FAC: [i16; 8] = [18431, 20479, 22527, 24575, 26623, 28671, 30719, 32767];
+
+// 16 bit saturating arithmetic as defined by the spec
+
+fn add(a: i16, b: i16) -> i16 {
+    a.saturating_add(b)
+}
+
+fn sub(a: i16, b: i16) -> i16 {
+    a.saturating_sub(b)
+}
+
+fn mult_r(a: i16, b: i16) -> i16 {
+    if a == i16::MIN && b == i16::MIN {
+        i16::MAX
+    } else {
+        ((a as i32 * b as i32 + 16384) >> 15) as i16
+    }
+}
+
+fn asr(a: i16, n: i16) -> i16 {
+    match n {
+        n if n >= 16 => -((a < 0) as i16),
+        n if n <= -16 => 0,
+        n if n < 0 => a << -n,
+        n => a >> n,
+    }
+}
+
+fn asl(a: i16, n: i16) -> i16 {
+    match n {
+        n if n >= 16 => 0,
+        n if n <= -16 => -((a < 0) as i16),
+        n if n < 0 => asr(a, -n),
+        n => a << n,
+    }
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize, // in bits
+}
+
+impl<'a> BitReader<'a> {
+    fn read(&mut self, bits: u32) -> i16 {
+        let mut val = 0;
+        for _ in 0..bits {
+            let bit = (self.data[self.pos / 8] >> (7 - self.pos % 8)) & 1;
+            val = (val << 1) | bit as i16;
+            self.pos += 1;
+        }
+        val
+    }
+}
+
+#[derive(Default)]
+struct Subframe {
+    nc: i16,        // LTP lag
+    bc: i16,        // LTP gain
+    mc: i16,        // RPE grid position
+    xmaxc: i16,     // RPE block amplitude
+    xmc: [i16; 13], // RPE pulses
+}
+
+struct Frame {
+    larc: [i16; 8], // log area ratios
+    subframes: [Subframe; 4],
+}
+
+impl Frame {
+    fn unpack(packet: &[u8]) -> Frame {
+        let mut bits = BitReader {
+            data: packet,
+            pos: 4, // signature
+        };
+
+        let mut larc = [0; 8];
+        for (lar, width) in larc.iter_mut().zip(LAR_BITS.iter()) {
+            *lar = bits.read(*width);
+        }
+
+        let mut subframes: [Subframe; 4] = Default::default();
+        for sub in subframes.iter_mut() {
+            sub.nc = bits.read(7);
+            sub.bc = bits.read(2);
+            sub.mc = bits.read(2);
+            sub.xmaxc = bits.read(6);
+            for xmc in sub.xmc.iter_mut() {
+                *xmc = bits.read(3);
+            }
+        }
+
+        Frame { larc, subframes }
+    }
+}
+
+// decodes whole packets to mono 16 bit samples. the filter state carries
+// over between calls, so sound data can be decoded in pieces
+#[derive(Debug)]
+pub struct Decoder {
+    dp0: [i16; 280], // reconstructed long term residual history
+    nrp: i16,
+    v: [i16; 9],
+    larpp: [[i16; 8]; 2],
+    j: usize,
+    msr: i16,
+}
+
+impl Default for Decoder {
+    fn default() -> Decoder {
+        Decoder::new()
+    }
+}
+
+impl Decoder {
+    pub fn new() -> Decoder {
+        Decoder {
+            dp0: [0; 280],
+            nrp: 40,
+            v: [0; 9],
+            larpp: [[0; 8]; 2],
+            j: 0,
+            msr: 0,
+        }
+    }
+
+    pub fn decode(&mut self, data: &[u8]) -> Vec<i16> {
+        let mut samples = vec![0; data.len() / PACKET_SIZE * FRAMES_PER_PACKET];
+        for (packet, out) in data
+            .chunks_exact(PACKET_SIZE)
+            .zip(samples.chunks_exact_mut(FRAMES_PER_PACKET))
+        {
+            self.decode_packet(packet, out);
+        }
+        samples
+    }
+
+    fn decode_packet(&mut self, packet: &[u8], out: &mut [i16]) {
+        let frame = Frame::unpack(packet);
+
+        let mut wt = [0i16; FRAMES_PER_PACKET];
+        for (sub, wt) in frame
+            .subframes
+            .iter()
+            .zip(wt.chunks_exact_mut(SUBFRAME_SIZE))
+        {
+            let erp = rpe_decoding(sub.xmaxc, sub.mc, &sub.xmc);
+            self.long_term_synthesis(sub.nc, sub.bc, &erp);
+            wt.copy_from_slice(&self.dp0[120..160]);
+        }
+
+        self.short_term_synthesis(&frame.larc, &wt, out);
+        self.postprocess(out);
+    }
+
+    fn long_term_synthesis(
+        &mut self,
+        ncr: i16,
+        bcr: i16,
+        erp: &[i16; SUBFRAME_SIZE],
+    ) {
+        // out of range lags reuse the previous one
+        let nr = if (40..=120).contains(&ncr) {
+            ncr
+        } else {
+            self.nrp
+        };
+        self.nrp = nr;
+
+        let brp = QLB[bcr as usize];
+        for (k, erp) in erp.iter().enumerate() {
+            let drpp = mult_r(brp, self.dp0[120 + k - nr as usize]);
+            self.dp0[120 + k] = add(*erp, drpp);
+        }
+
+        self.dp0.copy_within(40..160, 0);
+    }
+
+    fn short_term_synthesis(
+        &mut self,
+        larc: &[i16; 8],
+        wt: &[i16; FRAMES_PER_PACKET],
+        out: &mut [i16],
+    ) {
+        let prev = self.larpp[self.j];
+        self.j ^= 1;
+        self.larpp[self.j] = decode_lars(larc);
+        let cur = self.larpp[self.j];
+
+        // the reflection coefficients are interpolated from the previous
+        // frame over the first 40 samples
+        let mut larp = [0i16; 8];
+        for (idx, (start, end)) in
+            [(0, 13), (13, 27), (27, 40), (40, 160)].iter().enumerate()
+        {
+            for i in 0..8 {
+                let (p, c) = (prev[i], cur[i]);
+                larp[i] = match idx {
+                    0 => add(add(p >> 2, c >> 2), p >> 1),
+                    1 => add(p >> 1, c >> 1),
+                    2 => add(add(p >> 2, c >> 2), c >> 1),
+                    _ => c,
+                };
+            }
+            let rp = lar_to_rp(&larp);
+            self.short_term_filter(
+                &rp,
+                &wt[*start..*end],
+                &mut out[*start..*end],
+            );
+        }
+    }
+
+    fn short_term_filter(
+        &mut self,
+        rrp: &[i16; 8],
+        wt: &[i16],
+        sr: &mut [i16],
+    ) {
+        let v = &mut self.v;
+        for (sri, wt) in sr.iter_mut().zip(wt) {
+            let mut s = *wt;
+            for i in (0..8).rev() {
+                s = sub(s, mult_r(rrp[i], v[i]));
+                v[i + 1] = add(v[i], mult_r(rrp[i], s));
+            }
+            v[0] = s;
+            *sri = s;
+        }
+    }
+
+    // de-emphasis and upscaling
+    fn postprocess(&mut self, out: &mut [i16]) {
+        for sample in out.iter_mut() {
+            self.msr = add(*sample, mult_r(self.msr, 28180));
+            *sample = add(self.msr, self.msr) & !7;
+        }
+    }
+}
+
+fn rpe_decoding(xmaxc: i16, mc: i16, xmc: &[i16; 13]) -> [i16; SUBFRAME_SIZE] {
+    // split xmaxc into exponent and mantissa
+    let mut exp = if xmaxc > 15 { (xmaxc >> 3) - 1 } else { 0 };
+    let mut mant = xmaxc - (exp << 3);
+    if mant == 0 {
+        exp = -4;
+        mant = 7;
+    } else {
+        while mant <= 7 {
+            mant = mant << 1 | 1;
+            exp -= 1;
+        }
+        mant -= 8;
+    }
+
+    // inverse APCM quantization
+    let temp1 = FAC[mant as usize];
+    let temp2 = sub(6, exp);
+    let temp3 = asl(1, sub(temp2, 1));
+
+    // grid positioning
+    let mut ep = [0i16; SUBFRAME_SIZE];
+    for (i, xmc) in xmc.iter().enumerate() {
+        let temp = ((xmc << 1) - 7) << 12; // restore sign
+        let temp = add(mult_r(temp1, temp), temp3);
+        ep[mc as usize + 3 * i] = asr(temp, temp2);
+    }
+    ep
+}
+
+fn decode_lars(larc: &[i16; 8]) -> [i16; 8] {
+    // (B, MIC, INVA) for each coefficient
+    const STEPS: [(i16, i16, i16); 8] = [
+        (0, -32, 13107),
+        (0, -32, 13107),
+        (2048, -16, 13107),
+        (-2560, -16, 13107),
+        (94, -8, 19223),
+        (-1792, -8, 17476),
+        (-341, -4, 31454),
+        (-1144, -4, 29708),
+    ];
+
+    let mut larpp = [0; 8];
+    for (i, (b, mic, inva)) in STEPS.iter().enumerate() {
+        let temp = add(larc[i], *mic) << 10;
+        let temp = sub(temp, b << 1);
+        let temp = mult_r(*inva, temp);
+        larpp[i] = add(temp, temp);
+    }
+    larpp
+}
+
+// log area ratios to reflection coefficients
+fn lar_to_rp(larp: &[i16; 8]) -> [i16; 8] {
+    let mut rp = [0; 8];
+    for (rp, lar) in rp.iter_mut().zip(larp.iter()) {
+        let temp = if *lar == i16::MIN {
+            i16::MAX
+        } else {
+            lar.abs()
+        };
+        let temp = if temp < 11059 {
+            temp << 1
+        } else if temp < 20070 {
+            temp + 11059
+        } else {
+            add(temp >> 2, 26112)
+        };
+        *rp = if *lar < 0 { -temp } else { temp };
+    }
+    rp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // two consecutive packets with the samples a C transcription of the
+    // libgsm decoder produces from them. the second packet interpolates
+    // its reflection coefficients from the first and predicts from its
+    // long term residual
+    const PACKETS: [u8; 66] = [
+        0xd6, 0x67, 0x5b, 0x96, 0x2d, 0xb0, 0x8b, 0x8d, 0x87, 0x8c, 0xd0, 0x00,
+        0xb0, 0xe8, 0x3f, 0xdd, 0x6f, 0xc1, 0x8a, 0xf1, 0x0a, 0xe7, 0x27, 0xc3,
+        0xbd, 0x95, 0xdd, 0x0b, 0x95, 0xaf, 0x1c, 0x4c, 0x93, 0xd5, 0x5b, 0x94,
+        0x9a, 0x24, 0xc5, 0x0b, 0x27, 0x87, 0xaf, 0x7b, 0xa8, 0xd9, 0x68, 0x34,
+        0x8c, 0x04, 0x8e, 0x23, 0x94, 0x2a, 0xc2, 0x94, 0x52, 0x7b, 0xf9, 0x57,
+        0x6a, 0xe7, 0x0c, 0x78, 0x31, 0x90,
+    ];
+    const SAMPLES: [i16; 320] = [
+        -1792, -1736, -1312, -40, 120, -104, 1384, 1432, 1296, -544, -768,
+        -496, 1112, 1224, 312, 776, 1104, 1192, 856, 392, 216, -1120, -1256,
+        -952, -464, -280, -400, -1800, -1912, -712, -2544, -3472, -2512, -4184,
+        -4496, -3080, -4736, -5160, -3400, -2728, -2536, -1664, -680, -320,
+        360, 1184, 1968, 2160, 1864, 2752, 3072, 2120, 1472, 1120, 664, 640,
+        408, -336, -856, -632, -304, -544, -712, -464, 808, 1608, 1240, 1312,
+        1688, 1776, 680, -224, -240, 128, 96, -480, -1480, -1624, -800, -944,
+        -392, 328, 80, 216, 832, 1056, 1000, 928, 800, 824, 832, 584, 1784,
+        2400, 1496, 2472, 3432, 2688, 712, -112, 288, 1304, 1304, -24, -720,
+        -64, 768, 1800, 1720, 808, 2224, 3704, 3112, 1648, 1008, 1176, 1640,
+        1376, 160, -616, -1560, -1712, -8, -608, -2216, -1288, 1256, 2104,
+        1216, 1936, 2872, 1896, 3432, 4520, 2864, 400, -128, -616, -320, 32,
+        -2560, -5720, -4912, -3896, -4504, -4584, -5328, -3816, -904, -72,
+        -928, -920, 624, 784, 64, -104, -296, -440, -272, 280, -568, -1312, 24,
+        336, -168, 176, 1360, 1832, 1064, -728, -1512, -712, 1144, 1632, 488,
+        1048, 1728, 1656, 2528, 2256, 1256, 2216, 2536, 1960, 3624, 3832, 2872,
+        3944, 3616, 3048, 5184, 5088, 4376, 5832, 5328, 4768, 2008, -184, -824,
+        -1208, 160, 72, 32, 1128, 1344, 2432, 1560, 1056, 968, 704, 1120, 712,
+        -432, -984, -1488, -704, -312, -232, -184, -416, -72, -984, -1176,
+        -1216, -2008, -1776, -1960, -2976, -3792, -4184, -2392, -2016, -1424,
+        -1472, -1096, -8, 392, 1680, 1880, 1112, 1280, 1032, 888, 624, -1080,
+        -1776, -2208, -3232, -3216, -3400, -4080, -3848, -3528, -2768, -1920,
+        -1176, -1104, -832, -160, 336, 800, 1040, 1192, 1200, 1352, 2936, 3240,
+        3456, 4304, 3864, 3640, 4904, 4696, 4520, 5912, 5456, 5256, 3992, 2576,
+        1928, 1072, 704, -488, -80, 160, -24, 368, -608, -616, -144, -600,
+        -280, -1552, -2504, -2608, -2800, -2152, -2024, -1968, -2048, -1960,
+        -136, 744, 1432, 560, -8, 120, -360, 912, 856, -984, -800, -1408, -504,
+        1376, 1496, 1520, 2400, 2704, 1584,
+    ];
+
+    #[test]
+    fn decodes_consecutive_packets() {
+        let mut decoder = Decoder::new();
+        assert_eq!(decoder.decode(&PACKETS), &SAMPLES[..]);
+    }
+
+    #[test]
+    fn carries_state_between_calls() {
+        let mut decoder = Decoder::new();
+        let samples: Vec<i16> = PACKETS
+            .chunks(PACKET_SIZE)
+            .flat_map(|data| decoder.decode(data))
+            .collect();
+        assert_eq!(samples, &SAMPLES[..]);
+    }
+}
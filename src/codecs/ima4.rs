@@ -0,0 +1,225 @@
+// Apple IMA ADPCM ('ima4'). each channel is stored in 34 byte packets: a 2
+// byte header holding the upper 9 bits of the predictor and a 7 bit step
+// index, followed by 64 4-bit samples, low nibble first. packets for each
+// channel are interleaved, and num_sample_frames counts packets
+
+pub const PACKET_SIZE: usize = 34;
+pub const FRAMES_PER_PACKET: usize = 64;
+
+const INDEX_TABLE: [i8; 16] =
+    [-1, -1, -1, -1, 2, 4, 6, 8, -1, -1, -1, -1, 2, 4, 6, 8];
+
+const STEP_TABLE: [i32; 89] = [
+    7, 8, 9, 10, 11, 12, 13, 14, 16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41,
+    45, 50, 55, 60, 66, 73, 80, 88, 97, 107, 118, 130, 143, 157, 173, 190, 209,
+    230, 253, 279, 307, 337, 371, 408, 449, 494, 544, 598, 658, 724, 796, 876,
+    963, 1060, 1166, 1282, 1411, 1552, 1707, 1878, 2066, 2272, 2499, 2749,
+    3024, 3327, 3660, 4026, 4428, 4871, 5358, 5894, 6484, 7132, 7845, 8630,
+    9493, 10442, 11487, 12635, 13899, 15289, 16818, 18500, 20350, 22385, 24623,
+    27086, 29794, 32767,
+];
+
+#[derive(Debug, Default, Clone, Copy)]
+struct ChannelState {
+    predictor: i32,
+    index: usize,
+}
+
+impl ChannelState {
+    fn from_header(header: [u8; 2]) -> ChannelState {
+        let header = i16::from_be_bytes(header);
+        ChannelState {
+            predictor: (header & !0x7f) as i32,
+            index: ((header & 0x7f) as usize).min(STEP_TABLE.len() - 1),
+        }
+    }
+
+    fn header(&self) -> [u8; 2] {
+        let predictor = self.predictor as i16 & !0x7f;
+        (predictor | self.index as i16).to_be_bytes()
+    }
+
+    fn expand_nibble(&mut self, nibble: u8) -> i16 {
+        let step = STEP_TABLE[self.index];
+        let mut diff = step >> 3;
+        if nibble & 4 > 0 {
+            diff += step;
+        }
+        if nibble & 2 > 0 {
+            diff += step >> 1;
+        }
+        if nibble & 1 > 0 {
+            diff += step >> 2;
+        }
+        if nibble & 8 > 0 {
+            diff = -diff;
+        }
+
+        self.predictor = (self.predictor + diff)
+            .max(i16::MIN as i32)
+            .min(i16::MAX as i32);
+        self.index = (self.index as i32 + INDEX_TABLE[nibble as usize] as i32)
+            .max(0)
+            .min(STEP_TABLE.len() as i32 - 1) as usize;
+
+        self.predictor as i16
+    }
+
+    fn compress_sample(&mut self, sample: i16) -> u8 {
+        let mut diff = sample as i32 - self.predictor;
+        let mut nibble = 0;
+        if diff < 0 {
+            nibble = 8;
+            diff = -diff;
+        }
+
+        let mut step = STEP_TABLE[self.index];
+        let mut mask = 4;
+        while mask > 0 {
+            if diff >= step {
+                nibble |= mask;
+                diff -= step;
+            }
+            step >>= 1;
+            mask >>= 1;
+        }
+
+        // keep the predictor in sync with what the decoder will produce
+        self.expand_nibble(nibble);
+        nibble
+    }
+}
+
+fn decode_packet(packet: &[u8], out: &mut [i16; FRAMES_PER_PACKET]) {
+    let mut state = ChannelState::from_header([packet[0], packet[1]]);
+    for (idx, byte) in packet[2..PACKET_SIZE].iter().enumerate() {
+        out[idx * 2] = state.expand_nibble(byte & 0x0f);
+        out[idx * 2 + 1] = state.expand_nibble(byte >> 4);
+    }
+}
+
+// decodes whole packets to interleaved 16 bit samples. a trailing partial
+// packet is ignored
+pub fn decode(data: &[u8], num_channels: usize) -> Vec<i16> {
+    let packets_size = PACKET_SIZE * num_channels;
+    let frame_size = FRAMES_PER_PACKET * num_channels;
+    let mut samples =
+        Vec::with_capacity(data.len() / packets_size * frame_size);
+
+    let mut channel = [0i16; FRAMES_PER_PACKET];
+    for packets in data.chunks_exact(packets_size) {
+        let start = samples.len();
+        samples.resize(start + frame_size, 0);
+        for (ch, packet) in packets.chunks_exact(PACKET_SIZE).enumerate() {
+            decode_packet(packet, &mut channel);
+            for (frame, sample) in channel.iter().enumerate() {
+                samples[start + frame * num_channels + ch] = *sample;
+            }
+        }
+    }
+
+    samples
+}
+
+// encodes interleaved 16 bit samples into packets. the predictor and step
+// index carry over between packets, and frames that don't fill a packet are
+// held until more samples arrive or the encoder is flushed
+#[derive(Debug)]
+pub struct Encoder {
+    channels: Vec<ChannelState>,
+    pending: Vec<i16>,
+}
+
+impl Encoder {
+    pub fn new(num_channels: usize) -> Encoder {
+        Encoder {
+            channels: vec![ChannelState::default(); num_channels],
+            pending: Vec::with_capacity(FRAMES_PER_PACKET * num_channels),
+        }
+    }
+
+    // returns the number of packets written to `out`
+    pub fn encode(
+        &mut self,
+        samples: impl Iterator<Item = i16>,
+        out: &mut Vec<u8>,
+    ) -> u32 {
+        let frame_size = FRAMES_PER_PACKET * self.channels.len();
+        let mut packets = 0;
+        for sample in samples {
+            self.pending.push(sample);
+            if self.pending.len() == frame_size {
+                self.write_packets(out);
+                packets += 1;
+            }
+        }
+        packets
+    }
+
+    // pads any pending frames with silence to complete the last packet.
+    // returns the number of packets written to `out`
+    pub fn flush(&mut self, out: &mut Vec<u8>) -> u32 {
+        if self.pending.is_empty() {
+            return 0;
+        }
+        let frame_size = FRAMES_PER_PACKET * self.channels.len();
+        self.pending.resize(frame_size, 0);
+        self.write_packets(out);
+        1
+    }
+
+    fn write_packets(&mut self, out: &mut Vec<u8>) {
+        let num_channels = self.channels.len();
+        for (ch, state) in self.channels.iter_mut().enumerate() {
+            // the decoder starts from the truncated header predictor
+            let header = state.header();
+            *state = ChannelState::from_header(header);
+            out.extend_from_slice(&header);
+
+            let mut points = self.pending.iter().skip(ch).step_by(num_channels);
+            while let (Some(low), Some(high)) = (points.next(), points.next()) {
+                let low = state.compress_sample(*low);
+                let high = state.compress_sample(*high);
+                out.push(low | (high << 4));
+            }
+        }
+        self.pending.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_within_error_bound() {
+        // a stereo sine pair spanning 3.5 packets, so flush pads the last
+        let frames = FRAMES_PER_PACKET * 7 / 2;
+        let samples: Vec<i16> = (0..frames)
+            .flat_map(|frame| {
+                let t = frame as f64 / 64f64;
+                let left = (t * std::f64::consts::PI).sin() * 16000f64;
+                let right = (t * 3f64).sin() * 4000f64;
+                vec![left as i16, right as i16]
+            })
+            .collect();
+
+        let mut encoder = Encoder::new(2);
+        let mut data = Vec::new();
+        let packets = encoder.encode(samples.iter().copied(), &mut data)
+            + encoder.flush(&mut data);
+        assert_eq!(packets, 4);
+        assert_eq!(data.len(), 4 * 2 * PACKET_SIZE);
+
+        let decoded = decode(&data, 2);
+        assert_eq!(decoded.len(), 4 * FRAMES_PER_PACKET * 2);
+        // the step size starts at its smallest, so give it 16 frames to
+        // catch up with the signal
+        let errors = samples
+            .iter()
+            .zip(&decoded)
+            .skip(16 * 2)
+            .map(|(s, d)| (*s as i32 - *d as i32).abs());
+        assert!(errors.max().unwrap() < 256);
+    }
+}
@@ -0,0 +1,309 @@
+// MACE 3:1 ('MAC3') and 6:1 ('MAC6') decoding, adapted from the libavcodec
+// decoder. MAC3 packets are 2 bytes per channel and MAC6 packets are 1 byte
+// per channel, both holding 6 sample frames. packets for each channel are
+// interleaved, num_sample_frames counts packets, and the decoder state
+// carries over between packets
+
+pub const MAC3_PACKET_SIZE: usize = 2;
+pub const MAC6_PACKET_SIZE: usize = 1;
+pub const FRAMES_PER_PACKET: usize = 6;
+
+const TAB1: [i32; 8] = [-13, 8, 76, 222, 222, 76, 8, -13];
+
+const TAB3: [i32; 4] = [-18, 140, 140, -18];
+
+#[rustfmt::skip]
+const TAB2: [[i16; 4]; 128] = [
+    [37, 116, 206, 330], [39, 121, 216, 346], [41, 127, 225, 361],
+    [42, 132, 235, 377], [44, 137, 245, 392], [46, 144, 256, 409],
+    [48, 150, 267, 426], [50, 156, 278, 444], [52, 163, 290, 463],
+    [54, 170, 302, 483], [57, 177, 315, 504], [59, 185, 328, 525],
+    [62, 193, 342, 547], [64, 201, 357, 571], [67, 210, 372, 595],
+    [70, 219, 388, 620], [73, 228, 404, 646], [76, 238, 421, 674],
+    [79, 248, 439, 702], [83, 258, 458, 732], [86, 269, 477, 763],
+    [90, 281, 497, 795], [94, 293, 518, 829], [98, 305, 540, 864],
+    [102, 318, 563, 901], [106, 331, 587, 939], [111, 345, 612, 979],
+    [115, 360, 638, 1020], [120, 375, 665, 1063], [125, 391, 693, 1108],
+    [131, 408, 722, 1155], [136, 425, 753, 1204], [142, 443, 785, 1255],
+    [148, 461, 818, 1308], [154, 481, 852, 1364], [161, 501, 888, 1421],
+    [168, 522, 926, 1482], [175, 544, 965, 1544], [182, 567, 1006, 1610],
+    [190, 591, 1048, 1678], [198, 616, 1093, 1749], [206, 642, 1139, 1823],
+    [215, 669, 1187, 1900], [224, 698, 1237, 1980], [233, 727, 1289, 2064],
+    [243, 758, 1344, 2151], [254, 790, 1401, 2242], [264, 823, 1460, 2337],
+    [275, 858, 1522, 2436], [287, 894, 1586, 2539], [299, 932, 1653, 2646],
+    [312, 972, 1723, 2758], [325, 1013, 1796, 2874], [339, 1055, 1872, 2996],
+    [353, 1100, 1951, 3122], [368, 1146, 2033, 3254], [384, 1195, 2119, 3392],
+    [400, 1245, 2209, 3535], [417, 1298, 2302, 3684], [434, 1353, 2399, 3840],
+    [453, 1410, 2500, 4002], [472, 1470, 2606, 4171], [492, 1532, 2716, 4347],
+    [512, 1597, 2831, 4531], [534, 1664, 2950, 4722], [557, 1734, 3075, 4921],
+    [580, 1808, 3205, 5129], [605, 1884, 3340, 5346], [630, 1963, 3481, 5572],
+    [657, 2046, 3628, 5807], [685, 2133, 3781, 6052], [714, 2223, 3941, 6307],
+    [744, 2316, 4107, 6573], [775, 2414, 4281, 6851], [808, 2516, 4461, 7140],
+    [842, 2623, 4650, 7441], [878, 2733, 4846, 7755], [915, 2849, 5050, 8083],
+    [954, 2969, 5264, 8424], [994, 3095, 5486, 8780], [1036, 3226, 5718, 9150],
+    [1080, 3362, 5959, 9536], [1125, 3504, 6211, 9939],
+    [1173, 3652, 6473, 10358], [1222, 3806, 6746, 10796],
+    [1274, 3967, 7031, 11251], [1328, 4134, 7327, 11726],
+    [1384, 4309, 7637, 12221], [1442, 4490, 7959, 12736],
+    [1503, 4680, 8295, 13274], [1567, 4877, 8645, 13834],
+    [1633, 5083, 9010, 14418], [1702, 5298, 9390, 15027],
+    [1774, 5522, 9787, 15661], [1849, 5755, 10200, 16322],
+    [1927, 5998, 10630, 17011], [2008, 6251, 11079, 17729],
+    [2093, 6515, 11546, 18477], [2181, 6790, 12033, 19257],
+    [2273, 7076, 12541, 20070], [2369, 7375, 13070, 20917],
+    [2469, 7686, 13622, 21800], [2573, 8010, 14197, 22720],
+    [2682, 8348, 14796, 23679], [2795, 8701, 15421, 24678],
+    [2913, 9068, 16071, 25720], [3036, 9451, 16750, 26805],
+    [3164, 9850, 17456, 27937], [3298, 10265, 18193, 29116],
+    [3437, 10699, 18961, 30345], [3582, 11150, 19762, 31626],
+    [3733, 11621, 20596, 32767], [3891, 12111, 21465, 32767],
+    [4055, 12623, 22371, 32767], [4226, 13155, 23316, 32767],
+    [4405, 13711, 24300, 32767], [4591, 14289, 25325, 32767],
+    [4785, 14893, 26394, 32767], [4987, 15521, 27508, 32767],
+    [5197, 16176, 28669, 32767], [5416, 16859, 29879, 32767],
+    [5645, 17571, 31140, 32767], [5883, 18312, 32455, 32767],
+    [6131, 19085, 32767, 32767], [6390, 19891, 32767, 32767],
+    [6659, 20730, 32767, 32767], [6940, 21605, 32767, 32767],
+    [7233, 22517, 32767, 32767],
+];
+
+#[rustfmt::skip]
+const TAB4: [[i16; 2]; 128] = [
+    [64, 216], [67, 226], [70, 236], [74, 246], [77, 257], [80, 268], [84, 280],
+    [88, 294], [92, 307], [96, 321], [100, 334], [104, 350], [109, 365],
+    [114, 382], [119, 399], [124, 416], [130, 434], [136, 454], [142, 475],
+    [148, 495], [155, 519], [162, 541], [169, 564], [176, 590], [185, 617],
+    [193, 644], [201, 673], [210, 703], [220, 735], [230, 768], [240, 802],
+    [251, 838], [262, 875], [274, 915], [286, 956], [299, 998], [312, 1043],
+    [326, 1089], [341, 1138], [356, 1189], [372, 1242], [388, 1297],
+    [406, 1356], [424, 1416], [443, 1479], [462, 1546], [483, 1615],
+    [505, 1686], [527, 1761], [551, 1840], [576, 1922], [601, 2008],
+    [628, 2097], [656, 2190], [686, 2288], [716, 2389], [748, 2496],
+    [781, 2607], [816, 2724], [853, 2846], [891, 2973], [930, 3104],
+    [972, 3243], [1016, 3389], [1061, 3539], [1108, 3698], [1158, 3862],
+    [1209, 4035], [1264, 4216], [1320, 4403], [1379, 4599], [1441, 4806],
+    [1505, 5019], [1572, 5244], [1642, 5477], [1715, 5722], [1792, 5978],
+    [1872, 6245], [1955, 6522], [2043, 6813], [2134, 7118], [2229, 7436],
+    [2329, 7767], [2432, 8114], [2541, 8477], [2655, 8854], [2773, 9250],
+    [2897, 9663], [3026, 10094], [3162, 10546], [3303, 11016], [3450, 11508],
+    [3604, 12020], [3765, 12556], [3933, 13118], [4108, 13703], [4292, 14315],
+    [4483, 14953], [4683, 15621], [4892, 16318], [5111, 17046], [5339, 17807],
+    [5577, 18602], [5826, 19433], [6086, 20300], [6358, 21205], [6642, 22152],
+    [6938, 23141], [7248, 24173], [7571, 25252], [7909, 26380], [8262, 27557],
+    [8631, 28786], [9016, 30072], [9419, 31413], [9839, 32767], [10278, 32767],
+    [10737, 32767], [11216, 32767], [11717, 32767], [12240, 32767],
+    [12786, 32767], [13356, 32767], [13953, 32767], [14576, 32767],
+    [15226, 32767], [15906, 32767], [16615, 32767],
+];
+
+#[derive(Debug, Default, Clone, Copy)]
+struct ChannelState {
+    index: i32,
+    factor: i32,
+    prev2: i32,
+    previous: i32,
+    level: i32,
+}
+
+impl ChannelState {
+    // table 0 and 2 take 3 bit values, table 1 takes 2 bit values
+    fn read_table(&mut self, val: u8, table: usize) -> i32 {
+        let val = val as usize;
+        let row = ((self.index & 0x7f0) >> 4) as usize;
+        let (tab1, tab2): (&[i32], &[i16]) = match table {
+            1 => (&TAB3, &TAB4[row]),
+            _ => (&TAB1, &TAB2[row]),
+        };
+
+        // the upper half of the values mirror the lower half, negated
+        let stride = tab2.len();
+        let current = if val < stride {
+            tab2[val] as i32
+        } else {
+            -1 - tab2[2 * stride - val - 1] as i32
+        };
+
+        self.index = (self.index + tab1[val] - (self.index >> 5)).max(0);
+
+        current
+    }
+
+    fn chomp3(&mut self, val: u8, table: usize) -> i16 {
+        let current = broken_clip(self.read_table(val, table) + self.level);
+        self.level = current - (current >> 3);
+        to_16_bit(current)
+    }
+
+    fn chomp6(&mut self, val: u8, table: usize) -> [i16; 2] {
+        let current = self.read_table(val, table);
+
+        if (self.previous ^ current) >= 0 {
+            self.factor = (self.factor + 506).min(i16::MAX as i32);
+        } else if self.factor - 314 < i16::MIN as i32 {
+            self.factor = -(i16::MAX as i32);
+        } else {
+            self.factor -= 314;
+        }
+
+        let current = broken_clip(current + self.level);
+        self.level = (current * self.factor) >> 15;
+        let current = current >> 1;
+
+        let delta = (self.prev2 - current) >> 2;
+        let out = [
+            to_16_bit(self.previous + self.prev2 - delta),
+            to_16_bit(self.previous + current + delta),
+        ];
+        self.prev2 = self.previous;
+        self.previous = current;
+
+        out
+    }
+}
+
+// the original decoder clips the low end to -32767
+fn broken_clip(n: i32) -> i32 {
+    if n > i16::MAX as i32 {
+        i16::MAX as i32
+    } else if n < i16::MIN as i32 {
+        -(i16::MAX as i32)
+    } else {
+        n
+    }
+}
+
+// repeats the high byte in the low byte, truncating to 16 bits
+fn to_16_bit(n: i32) -> i16 {
+    ((n & 0xff00) | ((n >> 8) & 0xff)) as i16
+}
+
+// decodes whole packets to interleaved 16 bit samples. the channel state
+// carries over between calls, so sound data can be decoded in pieces
+#[derive(Debug)]
+pub struct Decoder {
+    channels: Vec<ChannelState>,
+    packet_size: usize,
+    decode_byte: fn(&mut ChannelState, u8, &mut Vec<i16>),
+}
+
+impl Decoder {
+    pub fn mace3(num_channels: usize) -> Decoder {
+        Decoder {
+            channels: vec![ChannelState::default(); num_channels],
+            packet_size: MAC3_PACKET_SIZE,
+            decode_byte: |state, byte, out| {
+                let vals = [byte & 7, (byte >> 3) & 3, byte >> 5];
+                for (table, val) in vals.iter().enumerate() {
+                    out.push(state.chomp3(*val, table));
+                }
+            },
+        }
+    }
+
+    pub fn mace6(num_channels: usize) -> Decoder {
+        Decoder {
+            channels: vec![ChannelState::default(); num_channels],
+            packet_size: MAC6_PACKET_SIZE,
+            decode_byte: |state, byte, out| {
+                let vals = [byte >> 5, (byte >> 3) & 3, byte & 7];
+                for (table, val) in vals.iter().enumerate() {
+                    out.extend_from_slice(&state.chomp6(*val, table));
+                }
+            },
+        }
+    }
+
+    pub fn decode(&mut self, data: &[u8]) -> Vec<i16> {
+        let num_channels = self.channels.len();
+        let packets_size = self.packet_size * num_channels;
+        let frame_size = FRAMES_PER_PACKET * num_channels;
+        let mut samples =
+            Vec::with_capacity(data.len() / packets_size * frame_size);
+
+        let mut channel = Vec::with_capacity(FRAMES_PER_PACKET);
+        for packets in data.chunks_exact(packets_size) {
+            let start = samples.len();
+            samples.resize(start + frame_size, 0);
+            for (ch, packet) in
+                packets.chunks_exact(self.packet_size).enumerate()
+            {
+                channel.clear();
+                for byte in packet {
+                    (self.decode_byte)(
+                        &mut self.channels[ch],
+                        *byte,
+                        &mut channel,
+                    );
+                }
+                for (frame, sample) in channel.iter().enumerate() {
+                    samples[start + frame * num_channels + ch] = *sample;
+                }
+            }
+        }
+
+        samples
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // packets with the interleaved samples a C transcription of the
+    // libavcodec decoder produces from them
+    const MAC3_STEREO: [u8; 16] = [
+        0xa3, 0x5c, 0x17, 0xe8, 0x90, 0x2f, 0xc4, 0x7b, 0x66, 0x01, 0xde, 0x39,
+        0xb2, 0x4d, 0x85, 0xf0,
+    ];
+    const MAC3_STEREO_SAMPLES: [i16; 48] = [
+        257, -1, 257, -1, 0, -1, -515, -1, -772, 0, -1, 0, 0, -258, -515, -1,
+        -2057, -515, -2057, 257, -1, 0, 771, 1542, -258, 257, 257, -1, 2827,
+        -772, 4112, 0, 4369, -258, 4369, 257, 5911, -772, 2313, -258, -772,
+        -2057, -4113, -1286, 1028, -3085, 5911, -3085,
+    ];
+
+    const MAC6_STEREO: [u8; 16] = [
+        0xc7, 0x18, 0x6e, 0xa2, 0x35, 0xfb, 0x90, 0x4c, 0x1f, 0xe3, 0xa8, 0x7d,
+        0x52, 0xc9, 0x0b, 0x64,
+    ];
+    const MAC6_STEREO_SAMPLES: [i16; 96] = [
+        -1, 0, -1, 0, -1, 0, -1, -1, -1, -1, 0, -1, 0, -1, 0, -1, 0, -1, 257,
+        -1, 257, -1, 0, 0, 0, 0, 0, 0, -1, 0, -1, -1, -1, -1, -258, 0, -515, 0,
+        -772, 257, -1029, 257, -1286, 257, -1029, 257, -772, -1, -258, -258, 0,
+        -515, 0, -515, -1, -1, -1, 257, -258, 771, -258, 1285, -515, 1799,
+        -515, 1799, -1, 1285, 257, 257, 771, -1029, 1028, -1800, 1028, -2057,
+        514, -1286, 0, -1, -1, 1028, 0, 2056, 514, 3084, 1028, 3598, 1542,
+        3855, 1799, 4112, 2570, 2313, 3855, -515,
+    ];
+
+    const MAC3_MONO: [u8; 8] = [0xa3, 0x5c, 0x17, 0xe8, 0x90, 0x2f, 0xc4, 0x7b];
+    const MAC3_MONO_SAMPLES: [i16; 24] = [
+        257, 257, 0, -515, -772, -1, -1, -772, -515, -258, 514, 257, 257, -515,
+        -2571, -2571, -1, 1285, -2571, -772, -2571, 2827, 257, 7967,
+    ];
+
+    #[test]
+    fn decodes_mace3() {
+        let mut decoder = Decoder::mace3(1);
+        assert_eq!(decoder.decode(&MAC3_MONO), MAC3_MONO_SAMPLES);
+        let mut decoder = Decoder::mace3(2);
+        assert_eq!(decoder.decode(&MAC3_STEREO), MAC3_STEREO_SAMPLES);
+    }
+
+    #[test]
+    fn decodes_mace6() {
+        let mut decoder = Decoder::mace6(2);
+        assert_eq!(decoder.decode(&MAC6_STEREO), MAC6_STEREO_SAMPLES);
+    }
+
+    #[test]
+    fn carries_state_between_calls() {
+        // 2 packets at a time
+        let mut decoder = Decoder::mace3(2);
+        let samples: Vec<i16> = MAC3_STEREO
+            .chunks(2 * MAC3_PACKET_SIZE * 2)
+            .flat_map(|data| decoder.decode(data))
+            .collect();
+        assert_eq!(samples, MAC3_STEREO_SAMPLES);
+    }
+}
@@ -0,0 +1,30 @@
+// decoders and encoders for AIFF-C compression types. decoders produce
+// 16 bit linear PCM, which is what the COMM sample size is set to for
+// these types
+use super::ids::{self, ChunkID};
+
+pub mod g711;
+pub mod gsm;
+pub mod ima4;
+pub mod mace;
+
+// the standard COMM compressionName for each type we can write
+pub fn compression_name(id: &ChunkID) -> Option<&'static [u8]> {
+    match id {
+        ids::NONE => Some(b"not compressed"),
+        ids::ULAW => Some(b"\xb5Law 2:1"), // mac roman 'µ'
+        ids::ALAW => Some(b"ALaw 2:1"),
+        ids::IMA4 => Some(b"IMA 4:1"),
+        _ => None,
+    }
+}
+
+// packet based types count num_sample_frames in packets rather than frames
+pub fn frames_per_packet(id: &ChunkID) -> u32 {
+    match id {
+        ids::IMA4 => ima4::FRAMES_PER_PACKET as u32,
+        ids::MAC3 | ids::MAC6 => mace::FRAMES_PER_PACKET as u32,
+        ids::GSM => gsm::FRAMES_PER_PACKET as u32,
+        _ => 1,
+    }
+}
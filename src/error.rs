@@ -0,0 +1,84 @@
+use super::{extended::ExtendedError, ids::ChunkID, reader::Warning};
+use std::{error, fmt, io};
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    ID3(id3::Error),
+    InvalidID(ChunkID),
+    InvalidFormType(ChunkID),
+    InvalidID3Version([u8; 2]),
+    InvalidSize(i32, i32),     // expected, got,
+    InvalidData(&'static str), // failed to parse something
+    MissingChunk(ChunkID),     // required, but not in the file or not read yet
+    SampleRate(ExtendedError), // not a usable number
+    UnsupportedCompression(ChunkID),
+    UnsupportedBitRate(i16),
+    SampleTypeTooSmall(i16), // bit width of the decoded sample points
+    ReadMode(&'static str),  // not available to this kind of reader
+    SpecViolation(Warning),  // only raised by strict readers
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "io error: {}", e),
+            Error::ID3(e) => write!(f, "invalid ID3 tag: {}", e),
+            Error::InvalidID(id) => {
+                write!(f, "invalid chunk id {}", fmt_id(id))
+            }
+            Error::InvalidFormType(id) => {
+                write!(f, "invalid form type {}", fmt_id(id))
+            }
+            Error::InvalidID3Version(v) => {
+                write!(f, "unsupported ID3 version 2.{}.{}", v[0], v[1])
+            }
+            Error::InvalidSize(expected, got) => {
+                write!(f, "invalid chunk size {}, expected {}", got, expected)
+            }
+            Error::InvalidData(what) => write!(f, "invalid {}", what),
+            Error::MissingChunk(id) => {
+                write!(f, "missing {} chunk", fmt_id(id))
+            }
+            Error::SampleRate(e) => write!(f, "invalid sample rate: {}", e),
+            Error::UnsupportedCompression(id) => {
+                write!(f, "unsupported compression type {}", fmt_id(id))
+            }
+            Error::UnsupportedBitRate(bits) => {
+                write!(f, "unsupported bit rate {}", bits)
+            }
+            Error::SampleTypeTooSmall(bits) => {
+                write!(f, "sample type can't hold {} bit sample points", bits)
+            }
+            Error::ReadMode(msg) => f.write_str(msg),
+            Error::SpecViolation(w) => write!(f, "{}", w),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::ID3(e) => Some(e),
+            Error::SampleRate(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl From<id3::Error> for Error {
+    fn from(e: id3::Error) -> Error {
+        Error::ID3(e)
+    }
+}
+
+fn fmt_id(id: &ChunkID) -> String {
+    format!("{:?}", String::from_utf8_lossy(id))
+}
@@ -1,69 +1,206 @@
-// a partially implemented IEEE 754 extended precision number parser
+// IEEE 754 extended precision numbers, as used for AIFF sample rates
 // https://en.wikipedia.org/wiki/Extended_precision#x86_extended_precision_format
-use log::error;
-use std::ops::Mul;
+use std::convert::TryInto;
+use std::{error, fmt};
 
 const EXP_BIAS: i16 = 16383;
 const FIRST_POS: u8 = 0b10000000;
+const EXP_MAX: u16 = 0x7fff; // infinity and NaN
 
-// when offset 0 && bias 0, MSB = x * 2^0
-fn read_binary_fraction(byte: u8, byte_offset: u32, bias: u8) -> f64 {
-    let mut res = 0f64;
-    for idx in 0..8 {
-        if ((byte << idx) & FIRST_POS) == FIRST_POS {
-            let power: i32 =
-                ((idx + bias as i32) + (8 * byte_offset) as i32) * -1;
-            res += 2f64.powi(power);
+// values that don't make sense as a sample rate, or don't fit in an f64
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtendedError {
+    NaN,
+    Infinity,
+    Overflow, // finite, but larger than f64::MAX
+    Negative, // only an error for sample rates
+}
+
+impl fmt::Display for ExtendedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExtendedError::NaN => f.write_str("not a number"),
+            ExtendedError::Infinity => f.write_str("infinite"),
+            ExtendedError::Overflow => f.write_str("too large for an f64"),
+            ExtendedError::Negative => f.write_str("negative"),
         }
     }
-    res
 }
 
-pub fn parse_extended_precision_bytes(b: [u8; 10]) -> Result<f64, ()> {
-    // println!(
-    //     r"decimal bits
-    //       {:08b} {:08b} {:08b} {:08b}
-    //       {:08b} {:08b} {:08b} {:08b}
-    //       {:08b} {:08b}",
-    //     b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7], b[8], b[9]
-    // );
-
-    let mut significand = 0f64;
-    for idx in 0..8 {
-        significand += read_binary_fraction(b[idx + 2], idx as u32, 0);
-    }
+impl error::Error for ExtendedError {}
 
+// the exact value as its sign and significand * 2^exponent, erroring only
+// for infinities and NaN. the integer bit is explicit, so unnormals (integer
+// bit clear with a non zero exponent) and pseudo denormals are valid numbers
+// too, as they are to the 68881 and SANE
+pub fn parse_extended_precision_exact(
+    b: [u8; 10],
+) -> Result<(bool, u64, i32), ExtendedError> {
     let is_neg = b[0] & FIRST_POS == FIRST_POS;
-    let mut b = b;
-    if is_neg {
-        b[0] ^= FIRST_POS; // XOR the first bit, setting it to 0
+    let exp = u16::from_be_bytes([b[0] & !FIRST_POS, b[1]]);
+    let significand = u64::from_be_bytes(b[2..].try_into().unwrap());
+
+    if exp == EXP_MAX {
+        // the integer bit is ignored, pseudo infinities included
+        return match significand << 1 {
+            0 => Err(ExtendedError::Infinity),
+            _ => Err(ExtendedError::NaN),
+        };
     }
 
-    match (b[0], b[1]) {
-        // if sig > 1, 63rd bit must have been set
-        (exp1, exp2) if significand.gt(&1f64) => {
-            let exp = u16::from_be_bytes([exp1, exp2]);
-            let res =
-                2f64.powi((exp as i16 - EXP_BIAS) as i32).mul(significand);
-
-            Ok(match is_neg {
-                true => res.mul(-1f64),
-                false => res,
-            })
-        }
-        // zero, denormal
-        (0, 0) => {
-            error!("bit format not yet implemented");
-            Err(())
+    // denormals use the smallest exponent
+    let exp = (exp.max(1) as i32) - EXP_BIAS as i32 - 63;
+    Ok((is_neg, significand, exp))
+}
+
+// decodes every bit pattern, rounding the 64 bit significand to the nearest
+// f64 (ties to even)
+pub fn parse_extended_precision_bytes(
+    b: [u8; 10],
+) -> Result<f64, ExtendedError> {
+    let (is_neg, mut significand, exp) = parse_extended_precision_exact(b)?;
+    let sign = (is_neg as u64) << 63;
+    if significand == 0 {
+        return Ok(f64::from_bits(sign));
+    }
+
+    // normalize so the leading 1 is the top bit, making it
+    // 1.fraction * 2^exp
+    let zeros = significand.leading_zeros();
+    significand <<= zeros;
+    let exp = exp + 63 - zeros as i32;
+    if exp > 1023 {
+        return Err(ExtendedError::Overflow);
+    }
+
+    // f64 has a 53 bit significand and exponents down to -1022, below which
+    // it loses precision as a denormal
+    let (shift, base) = if exp >= -1022 {
+        (11, ((exp + 1022) as u64) << 52)
+    } else {
+        (11 + (-1022 - exp) as u32, 0)
+    };
+    let mantissa = round_shift(significand, shift);
+
+    // the integer bit in mantissa adds one to the exponent, as does a
+    // significand that rounds up to the next power of 2
+    let bits = base + mantissa;
+    if bits >= (0x7ff << 52) {
+        return Err(ExtendedError::Overflow);
+    }
+    Ok(f64::from_bits(sign | bits))
+}
+
+// n >> shift, rounded to nearest, ties to even
+fn round_shift(n: u64, shift: u32) -> u64 {
+    if shift > 64 {
+        return 0; // less than half the smallest denormal
+    }
+    let (kept, rest) = match shift {
+        64 => (0, n),
+        _ => (n >> shift, n & ((1 << shift) - 1)),
+    };
+    let half = 1 << (shift - 1);
+    if rest > half || (rest == half && kept & 1 == 1) {
+        kept + 1
+    } else {
+        kept
+    }
+}
+
+// every f64 is exactly representable, the extended exponent range being
+// wide enough that f64 denormals are normal numbers in it
+pub fn encode_extended_precision_bytes(n: f64) -> [u8; 10] {
+    let bits = n.to_bits();
+    let sign = ((bits >> 63) as u16) << 15;
+    // f64 exponent is 11 bits with a bias of 1023
+    let exp = ((bits >> 52) & 0x7ff) as i16;
+    let fraction = bits & ((1u64 << 52) - 1);
+
+    let (exp, significand) = match exp {
+        0 if fraction == 0 => (0, 0),
+        // denormal, shift the leading 1 up to the integer bit
+        0 => {
+            let zeros = fraction.leading_zeros() as i16;
+            (EXP_BIAS - 1022 - (zeros - 11), fraction << zeros)
         }
-        // NaN and unsupported operations
-        (0b01111111, 0b11111111) => {
-            error!("bit format not handled");
-            Err(())
+        // infinity, or NaN keeping its payload
+        0x7ff => (EXP_MAX as i16, (1u64 << 63) | (fraction << 11)),
+        // the integer bit is implicit in f64 but explicit in extended
+        // precision
+        _ => (exp - 1023 + EXP_BIAS, (1u64 << 63) | (fraction << 11)),
+    };
+
+    let mut b = [0u8; 10];
+    b[0..2].copy_from_slice(&(sign | exp as u16).to_be_bytes());
+    b[2..].copy_from_slice(&significand.to_be_bytes());
+    b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // xorshift, so the f64s cover every exponent and sign
+    fn random_bits(seed: &mut u64) -> u64 {
+        *seed ^= *seed << 13;
+        *seed ^= *seed >> 7;
+        *seed ^= *seed << 17;
+        *seed
+    }
+
+    #[test]
+    fn round_trips_sample_rates() {
+        let rates = [
+            8000.0,
+            11025.0,
+            16000.0,
+            22050.0,
+            32000.0,
+            44100.0,
+            48000.0,
+            88200.0,
+            96000.0,
+            176400.0,
+            192000.0,
+            352800.0,
+            384000.0,
+            44100.0 / 1.001,
+            48000.0 / 1.001,
+            44100.0 * 1.001,
+            22254.545454,
+            11127.272727,
+            0.5,
+            1.0,
+        ];
+        for rate in rates.iter() {
+            let b = encode_extended_precision_bytes(*rate);
+            assert_eq!(parse_extended_precision_bytes(b), Ok(*rate));
         }
-        (_, _) => {
-            error!("bit format not supported");
-            Err(())
+        assert_eq!(
+            encode_extended_precision_bytes(44100.0),
+            [0x40, 0x0e, 0xac, 0x44, 0, 0, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn round_trips_any_f64() {
+        let mut seed = 0x2545_f491_4f6c_dd1d;
+        let edges = [0, 1, 0x000f_ffff_ffff_ffff, 0x0010_0000_0000_0000];
+        let randoms = (0..100_000).map(|_| random_bits(&mut seed));
+        for bits in edges.iter().copied().chain(randoms) {
+            for n in [f64::from_bits(bits), -f64::from_bits(bits)].iter() {
+                let parsed = parse_extended_precision_bytes(
+                    encode_extended_precision_bytes(*n),
+                );
+                if n.is_nan() {
+                    assert_eq!(parsed, Err(ExtendedError::NaN));
+                } else if n.is_infinite() {
+                    assert_eq!(parsed, Err(ExtendedError::Infinity));
+                } else {
+                    assert_eq!(parsed.map(f64::to_bits), Ok(n.to_bits()));
+                }
+            }
         }
     }
 }
@@ -1,8 +1,11 @@
-use super::reader::Buffer;
-use std::io::{Read, Seek, SeekFrom};
-
 pub type ChunkID = [u8; 4];
 
+// ids are printable ascii, which may be padded with trailing spaces but not
+// start with one
+pub fn is_valid(id: &ChunkID) -> bool {
+    id[0] != b' ' && id.iter().all(|c| (b' '..=b'~').contains(c))
+}
+
 pub const FORM: &ChunkID = b"FORM";
 pub const AIFF: &ChunkID = b"AIFF";
 pub const COMMON: &ChunkID = b"COMM";
@@ -21,6 +24,23 @@ pub const ANNOTATION: &ChunkID = b"ANNO";
 pub const AIFF_C: &ChunkID = b"AIFC";
 pub const FVER: &ChunkID = b"FVER"; // 'Format version' - for AIFF C
 
+// AIFF C compression types
+pub const NONE: &ChunkID = b"NONE";
+pub const TWOS: &ChunkID = b"twos"; // big endian, same as NONE
+pub const SOWT: &ChunkID = b"sowt"; // little endian
+pub const FL32: &ChunkID = b"fl32"; // 32 bit IEEE 754 float
+pub const FL32_UPPER: &ChunkID = b"FL32";
+pub const FL64: &ChunkID = b"fl64"; // 64 bit IEEE 754 float
+pub const FL64_UPPER: &ChunkID = b"FL64";
+pub const ULAW: &ChunkID = b"ulaw"; // G.711 μ-law 2:1
+pub const ULAW_UPPER: &ChunkID = b"ULAW";
+pub const ALAW: &ChunkID = b"alaw"; // G.711 A-law 2:1
+pub const ALAW_UPPER: &ChunkID = b"ALAW";
+pub const IMA4: &ChunkID = b"ima4"; // Apple IMA ADPCM 4:1
+pub const MAC3: &ChunkID = b"MAC3"; // MACE 3:1
+pub const MAC6: &ChunkID = b"MAC6"; // MACE 6:1
+pub const GSM: &ChunkID = b"GSM "; // GSM 06.10 full rate
+
 pub const CHAN: &ChunkID = b"CHAN";
 pub const BASC: &ChunkID = b"basc";
 pub const TRNS: &ChunkID = b"trns";
@@ -28,3 +48,5 @@ pub const CATE: &ChunkID = b"cate";
 
 pub const TAG: &[u8; 3] = b"TAG"; // id3 v1
 pub const ID3: &[u8; 3] = b"ID3"; // id3 v2
+pub const ID3_CHUNK: &ChunkID = b"ID3 "; // an id3 v2 tag in a chunk
+pub const ID3_CHUNK_LOWER: &ChunkID = b"id3 ";
@@ -4,9 +4,16 @@ extern crate log;
 extern crate seek_bufread;
 
 mod chunks;
+mod codecs;
+mod error;
 mod extended;
-mod ids;
+pub mod ids;
 pub mod reader;
 mod samples;
+pub mod writer;
+
+pub use error::Error;
+pub use extended::ExtendedError;
+pub use samples::SampleType;
 
 mod test {}
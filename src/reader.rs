@@ -1,14 +1,57 @@
 use super::{
-    chunks::{self, Chunk, FormChunk},
-    ids,
-    samples::SampleType,
+    chunks::{self, Chunk, CommonChunk, FormChunk, SoundDataChunk},
+    codecs::{self, g711, gsm, ima4, mace},
+    error::Error,
+    ids::{self, ChunkID},
+    samples::{self, SampleType},
 };
+use log::{debug, trace, warn};
 use seek_bufread::BufReader;
-use std::io::{Read, Seek, SeekFrom};
+use std::collections::VecDeque;
+use std::convert::TryInto;
+use std::fmt;
+use std::io::{self, BufRead, Read, Seek, SeekFrom};
+use std::marker::PhantomData;
+use std::ops::Range;
+use std::slice::{ChunksExact, ChunksExactMut};
+use std::time::Duration;
 
 pub type Buffer<'a, Source> = &'a mut BufReader<Source>;
 
-// TODO samples iterator, enable seeking by duration fn
+// how the reader handles files that don't follow the spec, eg wrong FORM
+// sizes, missing pad bytes or trailing junk
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReadOptions {
+    // fail on any spec violation rather than recovering from it
+    pub strict: bool,
+    // skip over the sound data in `read` and decode it on demand with
+    // `read_frames`
+    pub lazy: bool,
+    // skip over the sound data, MIDI and APPL chunks without reading them,
+    // for scanning files for their metadata
+    pub metadata_only: bool,
+}
+
+// a spec violation a lenient reader recovered from
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub chunk_id: ChunkID,
+    pub offset: u64, // of the chunk, or the problem if it isn't in one
+    pub description: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} ({:?} chunk at byte {})",
+            self.description,
+            String::from_utf8_lossy(&self.chunk_id),
+            self.offset
+        )
+    }
+}
+
 // TODO diffeerent types of reader structs?
 // AiffAudioReader / AiffCompleteReader (id3 optional)
 pub struct AiffReader<Source> {
@@ -16,161 +59,463 @@ pub struct AiffReader<Source> {
     form_chunk: Option<FormChunk>,
     // pub id3v1_tags: Vec<chunks::ID3v1Chunk>, // should this be optional? or separate
     id3v2_tags: Vec<chunks::ID3v2Chunk>, // should this be optional? or separate
+    options: ReadOptions,
+    warnings: Vec<Warning>,
+    sound_range: Option<Range<u64>>, // of the SSND sound data in the source
+    stream: Option<SoundStream>,
 }
 
 impl<Source: Read + Seek> AiffReader<Source> {
     pub fn new(s: Source) -> AiffReader<Source> {
+        AiffReader::with_options(s, ReadOptions::default())
+    }
+
+    pub fn new_lazy(s: Source) -> AiffReader<Source> {
+        AiffReader::with_options(
+            s,
+            ReadOptions {
+                lazy: true,
+                ..ReadOptions::default()
+            },
+        )
+    }
+
+    pub fn with_options(s: Source, options: ReadOptions) -> AiffReader<Source> {
         AiffReader {
             buf: BufReader::new(s),
             form_chunk: None,
             id3v2_tags: vec![],
             // id3v1_tags: vec![],
+            options,
+            warnings: vec![],
+            sound_range: None,
+            stream: None,
         }
     }
 
-    pub fn read(&mut self) -> Result<(), chunks::ChunkError> {
-        let form_id = read_chunk_id(&mut self.buf);
+    // the spec violations recovered from by the last `read`
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    pub fn read(&mut self) -> Result<(), Error> {
+        self.warnings.clear();
+        let form_start = self.buf.position();
+        let file_len = self.buf.seek(SeekFrom::End(0))?;
+        self.buf.seek(SeekFrom::Start(form_start))?;
+        let form_id = read_chunk_id(&mut self.buf)?;
         let mut form = FormChunk::parse(&mut self.buf, form_id)?;
+        let form_end = form_start + 8 + form.size().max(0) as u64;
+        let mut past_form_end = false;
 
-        while self.buf.available() >= 4 {
-            let id = read_chunk_id(&mut self.buf);
+        while self.has_chunk()? {
+            let start = self.buf.position();
+            let id = read_chunk_id(&mut self.buf)?;
+            // chunk parsers read it again
+            let size = match self.peek(4)?[..] {
+                [a, b, c, d] => Some(i32::from_be_bytes([a, b, c, d])),
+                _ => None,
+            };
+
+            if self.read_tag(&id, start, file_len)? {
+                // not AIFF chunks, so there's no size to check
+                continue;
+            }
+
+            // whether the declared size is possible, which an id made of
+            // junk bytes seldom has
+            let in_file = size.is_some_and(|size| {
+                size >= 0 && start + 8 + size as u64 <= file_len
+            });
+
+            if start >= form_end && !past_form_end {
+                if !ids::is_valid(&id) || !in_file {
+                    self.trailing_data(start, file_len)?;
+                    break;
+                }
+                // the chunks are fine, the FORM size is wrong
+                past_form_end = true;
+                self.violation(
+                    *ids::FORM,
+                    form_start,
+                    format!(
+                        "FORM size {} doesn't cover its chunks",
+                        form.size()
+                    ),
+                )?;
+            }
+
+            debug!(
+                "{:?} chunk at byte {}",
+                String::from_utf8_lossy(&id),
+                start
+            );
 
             // once the common and form are detected, we can loop
             // buffer position is right past the id
             match &id {
                 ids::COMMON => {
-                    println!("Common chunk detected");
-                    let common =
-                        chunks::CommonChunk::parse(&mut self.buf, id).unwrap();
-                    println!(
-                        "channels {} frames {} bit rate {} sample rate {}",
+                    let common = chunks::CommonChunk::parse(
+                        &mut self.buf,
+                        id,
+                        form.form_type(),
+                    )?;
+                    if form.is_aiff_c()
+                        && common.size < chunks::AIFC_COMMON_MIN_SIZE
+                    {
+                        self.violation(
+                            id,
+                            start,
+                            "COMM chunk has no AIFC compression type",
+                        )?;
+                    }
+                    debug!(
+                        "channels {} frames {} bit rate {} sample rate {} \
+                         compression {:?}",
                         common.num_channels,
                         common.num_sample_frames,
                         common.bit_rate,
-                        common.sample_rate
+                        common.sample_rate,
+                        String::from_utf8_lossy(&common.compression_type)
                     );
                     form.set_common(common);
                 }
                 ids::SOUND => {
-                    let sound =
-                        chunks::SoundDataChunk::parse(&mut self.buf, id)
-                            .unwrap();
-                    println!(
-                        "SOUND chunk detected size {} offset {} block size {}",
+                    let mut sound = chunks::SoundDataChunk::parse_header(
+                        &mut self.buf,
+                        id,
+                    )?;
+                    debug!(
+                        "sound data size {} offset {} block size {}",
                         sound.size, sound.offset, sound.block_size
                     );
+                    let pos = self.buf.position();
+                    let mut len = sound.sound_size() as u64;
+                    if pos + len > file_len {
+                        // keep what there is of truncated sound data
+                        self.violation(
+                            id,
+                            start,
+                            "sound data runs past the end of the file",
+                        )?;
+                        len = file_len.saturating_sub(pos);
+                    }
+
+                    if self.options.lazy || self.options.metadata_only {
+                        self.sound_range = Some(pos..pos + len);
+                        self.buf.seek(SeekFrom::Current(len as i64))?;
+                    } else {
+                        sound.sound_data =
+                            read_bytes(&mut self.buf, len as usize)?;
+                    }
                     form.set_sound(sound);
                 }
                 ids::MARKER => {
-                    let mark =
-                        chunks::MarkerChunk::parse(&mut self.buf, id).unwrap();
-                    println!("MARKER chunk detected {:?}", mark);
+                    let mark = chunks::MarkerChunk::parse(&mut self.buf, id)?;
+                    trace!("{:?}", mark);
                     form.add_marker_chunk(mark);
                 }
                 ids::INSTRUMENT => {
                     let inst =
-                        chunks::InstrumentChunk::parse(&mut self.buf, id)
-                            .unwrap();
-                    println!("INSTRUMENT chunk detected {:?}", inst);
+                        chunks::InstrumentChunk::parse(&mut self.buf, id)?;
+                    trace!("{:?}", inst);
                     form.set_instrument(inst);
                 }
+                ids::MIDI | ids::APPLICATION if self.options.metadata_only => {
+                    debug!("skipping {:?} data", String::from_utf8_lossy(&id));
+                }
                 ids::MIDI => {
-                    let midi = chunks::MIDIDataChunk::parse(&mut self.buf, id)
-                        .unwrap();
-                    println!("MIDI chunk detected {:?}", midi);
+                    let midi = chunks::MIDIDataChunk::parse(&mut self.buf, id)?;
+                    trace!("{:?}", midi);
                     form.add_midi_chunk(midi);
                 }
                 ids::RECORDING => {
                     let rec =
-                        chunks::AudioRecordingChunk::parse(&mut self.buf, id)
-                            .unwrap();
-                    println!("RECORDING chunk detected {:?}", rec);
+                        chunks::AudioRecordingChunk::parse(&mut self.buf, id)?;
+                    trace!("{:?}", rec);
                     form.set_recording(rec);
                 }
                 ids::APPLICATION => {
                     let app = chunks::ApplicationSpecificChunk::parse(
                         &mut self.buf,
                         id,
-                    )
-                    .unwrap();
-                    println!("APPLICATION chunk detected {:?}", app);
+                    )?;
+                    trace!("{:?}", app);
                     form.add_app_chunk(app);
                 }
                 ids::COMMENTS => {
-                    let comm = chunks::CommentsChunk::parse(&mut self.buf, id)
-                        .unwrap();
-                    println!("COMMENT chunk detected {:?}", comm);
+                    let comm = chunks::CommentsChunk::parse(&mut self.buf, id)?;
+                    trace!("{:?}", comm);
                     form.set_comments(comm);
                 }
                 ids::NAME | ids::AUTHOR | ids::COPYRIGHT | ids::ANNOTATION => {
-                    let text =
-                        chunks::TextChunk::parse(&mut self.buf, id).unwrap();
-                    println!("TEXT chunk detected: {}", text.text);
+                    let text = chunks::TextChunk::parse(&mut self.buf, id)?;
+                    trace!("text {:?}", text.text);
                     form.add_text_chunk(text);
                 }
                 ids::FVER => {
-                    unimplemented!("FVER chunk detected");
+                    let fver =
+                        chunks::FormatVersionChunk::parse(&mut self.buf, id)?;
+                    trace!("{:?}", fver);
+                    form.set_format_version(fver);
                 }
-                // 3 bytes "ID3" identifier
-                // TODO merge both options
                 // ID3 chunks aren't stored in the FORM chunk. should they
                 // be stored next to the form chunk in the reader?
-                [73, 68, 51, _] => {
-                    self.buf.seek(SeekFrom::Current(-4)).unwrap();
+                ids::ID3_CHUNK | ids::ID3_CHUNK_LOWER => {
                     match chunks::ID3v2Chunk::parse(&mut self.buf, id) {
                         Ok(chunk) => self.id3v2_tags.push(chunk),
-                        Err(e) => {
-                            println!("Build ID3 chunk failed {:?}", e);
-                            self.buf.seek(SeekFrom::Current(3)).unwrap();
-                        }
+                        Err(e) => self.violation(
+                            id,
+                            start,
+                            format!("ID3 tag skipped: {}", e),
+                        )?,
                     }
                 }
-                [_, 73, 68, 51] => {
-                    self.buf.seek(SeekFrom::Current(-3)).unwrap();
-                    match chunks::ID3v2Chunk::parse(&mut self.buf, id) {
-                        Ok(chunk) => self.id3v2_tags.push(chunk),
-                        Err(e) => {
-                            println!("Build ID3 chunk failed {:?}", e);
-                            self.buf.seek(SeekFrom::Current(3)).unwrap();
-                        }
+                // unparsed, apple specific ones like CHAN included. an id
+                // that only looks valid, with a size running past the end of
+                // the file, is junk
+                id if ids::is_valid(id) && in_file => {
+                    debug!(
+                        "keeping unknown chunk {:?}",
+                        String::from_utf8_lossy(id)
+                    );
+                    let mut raw = chunks::RawChunk::parse_header(
+                        &mut self.buf,
+                        *id,
+                        start,
+                    )?;
+                    if !self.options.lazy && !self.options.metadata_only {
+                        raw.data =
+                            Some(read_bytes(&mut self.buf, raw.size as usize)?);
                     }
+                    form.add_raw_chunk(raw);
                 }
-                [84, 65, 71, _] => println!("v1 id3"), // "TAG_"
-                [_, 84, 65, 71] => println!("v1 id3"), // "_TAG"
-                ids::CHAN | ids::BASC | ids::TRNS | ids::CATE => {
-                    unimplemented!("apple stuff detected")
+                _ => {
+                    self.trailing_data(start, file_len)?;
+                    break;
                 }
-                id => println!(
-                    "other chunk {:?} {:?}",
-                    id,
-                    String::from_utf8_lossy(id)
-                ),
-                // _ => (),
             };
+            let size =
+                size.ok_or(io::Error::from(io::ErrorKind::UnexpectedEof))?;
+            self.end_chunk(id, start, size, file_len)?;
         }
+
+        let end = self.buf.position() + self.buf.available() as u64;
+        if self.buf.available() > 0 {
+            self.trailing_data(self.buf.position(), file_len)?;
+        }
+        if end < form_end {
+            self.violation(
+                *ids::FORM,
+                form_start,
+                format!(
+                    "FORM size {} runs past the end of the file",
+                    form.size()
+                ),
+            )?;
+        }
+        if form.common().is_none() {
+            self.violation(*ids::COMMON, end, "missing COMM chunk")?;
+        }
+        // AIFC requires one, even though there's only the one version
+        if form.is_aiff_c() && form.format_version().is_none() {
+            self.violation(*ids::FVER, end, "missing FVER chunk")?;
+        }
+
         self.form_chunk = Some(form);
+        Ok(())
+    }
+
+    // tags outside of any chunk, which taggers append to the file, possibly
+    // after the pad byte of an odd sized last chunk. returns false if there
+    // isn't one at `start`
+    fn read_tag(
+        &mut self,
+        id: &ChunkID,
+        start: u64,
+        file_len: u64,
+    ) -> Result<bool, Error> {
+        // ID3v1 tags are the last 128 bytes, "TAG" then the fields
+        let v1_start = match id {
+            [84, 65, 71, _] => start,
+            [0, 84, 65, 71] => start + 1,
+            _ => file_len,
+        };
+        if v1_start + 128 == file_len {
+            debug!("ID3v1 tag at byte {}", v1_start);
+            self.buf.seek(SeekFrom::Start(file_len))?;
+            return Ok(true);
+        }
+
+        let v2_start = match id {
+            // "ID3" then the major version, where a chunk id has a space
+            [73, 68, 51, version] if *version < b' ' => start,
+            [0, 73, 68, 51] => start + 1,
+            _ => return Ok(false),
+        };
+        self.buf.seek(SeekFrom::Start(v2_start))?;
+        match chunks::ID3v2Chunk::from_reader(&mut self.buf) {
+            Ok(chunk) => self.id3v2_tags.push(chunk),
+            Err(e) => {
+                self.violation(
+                    *id,
+                    v2_start,
+                    format!("ID3 tag skipped: {}", e),
+                )?;
+                self.buf.seek(SeekFrom::Start(v2_start + 3))?;
+            }
+        }
+        Ok(true)
+    }
+
+    // strict readers fail on a spec violation, lenient ones record it and
+    // carry on
+    fn violation(
+        &mut self,
+        chunk_id: ChunkID,
+        offset: u64,
+        description: impl Into<String>,
+    ) -> Result<(), Error> {
+        let warning = Warning {
+            chunk_id,
+            offset,
+            description: description.into(),
+        };
+        if self.options.strict {
+            return Err(Error::SpecViolation(warning));
+        }
+        warn!("{}", warning);
+        self.warnings.push(warning);
+        Ok(())
+    }
+
+    // junk that isn't a chunk, everything from here on is ignored
+    fn trailing_data(
+        &mut self,
+        offset: u64,
+        file_len: u64,
+    ) -> Result<(), Error> {
+        let len = file_len.saturating_sub(offset);
+        self.buf.seek(SeekFrom::Start(file_len))?;
+        self.violation(
+            *ids::FORM,
+            offset,
+            format!("{} byte(s) of trailing data", len),
+        )
+    }
 
-        // FIXME handle remaining bytes
-        println!("buffer complete {} byte(s) left", self.buf.available());
-        // set position to end?
+    // moves past the chunk starting at `start`, whatever its parser read,
+    // including the pad byte after odd sized chunks
+    fn end_chunk(
+        &mut self,
+        id: ChunkID,
+        start: u64,
+        size: i32,
+        file_len: u64,
+    ) -> Result<(), Error> {
+        let pos = self.buf.position();
+        if size < 0 {
+            return Err(Error::InvalidData("chunk size"));
+        }
+        let end = start + 8 + size as u64;
+        if pos > end + size as u64 % 2 {
+            self.violation(
+                id,
+                start,
+                format!("chunk size {} is smaller than its contents", size),
+            )?;
+        }
+        self.buf.seek(SeekFrom::Start(end))?;
 
+        // a truncated chunk has already been reported, pad byte and all
+        if size % 2 > 0 && end <= file_len {
+            // the next chunk's id may start right away, without a pad byte
+            let next = self.peek(5)?;
+            let id_at = |i: usize| {
+                next.len() >= i + 4
+                    && ids::is_valid(next[i..i + 4].try_into().unwrap())
+            };
+            let missing = next.is_empty() || (id_at(0) && !id_at(1));
+            if missing {
+                self.violation(id, start, "missing pad byte")?;
+            } else {
+                self.buf.seek(SeekFrom::Current(1))?;
+            }
+        }
         Ok(())
     }
 
+    // up to len bytes from the current position, without moving past them
+    fn peek(&mut self, len: usize) -> Result<Vec<u8>, Error> {
+        let mut bytes = Vec::with_capacity(len);
+        (&mut self.buf).take(len as u64).read_to_end(&mut bytes)?;
+        // relative, so it stays in the buffer. seeking back from the start
+        // always refills it
+        self.buf.seek(SeekFrom::Current(-(bytes.len() as i64)))?;
+        Ok(bytes)
+    }
+
+    // `available` only counts buffered bytes, which runs out after seeking
+    // past or reading through a large chunk
+    fn has_chunk(&mut self) -> Result<bool, Error> {
+        if self.buf.available() == 0 {
+            self.buf.fill_buf()?;
+        }
+        Ok(self.buf.available() >= 4)
+    }
+
     pub fn form(&self) -> &Option<FormChunk> {
         &self.form_chunk
     }
 
-    // TODO need to check available
-    // TODO return result iterator or complete buffer of data
-    // TODO pack frams
-    // should return a generic AiffSample<u8/u16/u32> etc
-    // TODO samples is most likely integers
+    pub fn samples<T: SampleType>(&self) -> Result<Vec<T>, Error> {
+        Ok(self.iter_samples()?.collect())
+    }
+
+    // sample points scaled to [-1.0, 1.0] from whatever the bit depth or
+    // compression type is
+    pub fn samples_f32(&self) -> Result<Vec<f32>, Error> {
+        self.samples()
+    }
+
+    pub fn samples_f64(&self) -> Result<Vec<f64>, Error> {
+        self.samples()
+    }
+
+    // the samples deinterleaved into one buffer per channel
+    pub fn samples_planar<T: SampleType>(&self) -> Result<Vec<Vec<T>>, Error> {
+        let samples = self.iter_samples()?;
+        let num_channels = samples.decoder.num_channels;
+        let mut channels: Vec<Vec<T>> = (0..num_channels)
+            .map(|_| Vec::with_capacity(samples.len() / num_channels))
+            .collect();
+        for (point, sample) in samples.enumerate() {
+            channels[point % num_channels].push(sample);
+        }
+        Ok(channels)
+    }
 
-    pub fn samples<T: SampleType>(&self) -> Vec<T> {
-        let f = self.form_chunk.as_ref().unwrap();
-        let s = f.sound().as_ref().unwrap();
-        let c = f.common().as_ref().unwrap();
+    // the samples grouped into sample frames, one sample point per channel
+    pub fn frames<T: SampleType>(&self) -> Result<Frames<T>, Error> {
+        Ok(Frames {
+            samples: self.samples()?,
+            num_channels: self.required_common()?.num_channels as usize,
+        })
+    }
+
+    pub fn iter_samples<T: SampleType>(&self) -> Result<Samples<'_, T>, Error> {
+        let c = self.required_common()?;
+        let s = self.required_sound()?;
+        if self.options.lazy {
+            return Err(Error::ReadMode(
+                "sound data isn't loaded by lazy readers, use read_frames",
+            ));
+        }
+        if self.options.metadata_only {
+            return Err(Error::ReadMode(
+                "sound data isn't loaded by metadata only readers",
+            ));
+        }
 
         // a sample point is the sound data for a single channel of audio
         // sample points containn <bit_rate> bits of data
@@ -178,97 +523,895 @@ impl<Source: Read + Seek> AiffReader<Source> {
         // playback occurs at <sample_rate> frames per second
         // num samples is always > 0 so shouldn't be any conversion issues
         // maybe it should be stored as a u16?
+        let decoder = SoundDecoder::new(c)?;
+        decoder.check_sample_type::<T>()?;
         let sample_points =
-            (c.num_sample_frames * c.num_channels as u32) as usize;
-        println!("sample points {:?}", sample_points);
+            (c.frame_count() as usize).saturating_mul(decoder.num_channels);
+        trace!("sample points {}", sample_points);
+        Ok(Samples::new(decoder, s.sample_data(), sample_points))
+    }
+
+    // lazy readers only. fills `samples` with as many whole frames of
+    // interleaved sample points as fit, reading them from the source.
+    // returns the number of frames read, which is 0 once all have been.
+    // errors if `samples` can't hold a single frame
+    pub fn read_frames<T: SampleType>(
+        &mut self,
+        samples: &mut [T],
+    ) -> Result<usize, Error> {
+        self.init_stream()?;
+        let stream = self.stream.as_mut().unwrap();
+        stream.decoder.check_sample_type::<T>()?;
+        if samples.len() < stream.decoder.num_channels {
+            return Err(Error::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "buffer is smaller than a sample frame",
+            )));
+        }
+        Ok(stream.read(&mut self.buf, samples)?)
+    }
+
+    // lazy readers only. moves to the start of sample frame `frame`, or the
+    // end if it's past the last one, and returns the new frame position.
+    // MACE and GSM carry state between packets, so they decode up to the
+    // frame, from the start when seeking backwards
+    pub fn seek_frame(&mut self, frame: u64) -> Result<u64, Error> {
+        self.init_stream()?;
+        let stream = self.stream.as_mut().unwrap();
+        Ok(stream.seek(&mut self.buf, frame)?)
+    }
+
+    // lazy readers only. moves to the sample frame playing at `time`
+    pub fn seek_time(&mut self, time: Duration) -> Result<u64, Error> {
+        let rate = self.required_common()?.exact_sample_rate;
+        // past the end when it's too far to count
+        self.seek_frame(rate.duration_to_frames(time).unwrap_or(u64::MAX))
+    }
+
+    fn init_stream(&mut self) -> Result<(), Error> {
+        if self.stream.is_some() {
+            return Ok(());
+        }
+        if !self.options.lazy {
+            return Err(Error::ReadMode(
+                "streaming sound data requires a lazy reader",
+            ));
+        }
+        let stream = SoundStream::new(
+            self.required_common()?,
+            self.required_sound()?,
+            self.sound_range
+                .clone()
+                .ok_or(Error::MissingChunk(*ids::SOUND))?,
+        )?;
+        self.stream = Some(stream);
+        Ok(())
+    }
+
+    fn required_common(&self) -> Result<&CommonChunk, Error> {
+        let f = self.form_chunk.as_ref();
+        f.and_then(|f| f.common().as_ref())
+            .ok_or(Error::MissingChunk(*ids::COMMON))
+    }
+
+    fn required_sound(&self) -> Result<&SoundDataChunk, Error> {
+        let f = self.form_chunk.as_ref();
+        f.and_then(|f| f.sound().as_ref())
+            .ok_or(Error::MissingChunk(*ids::SOUND))
+    }
+}
+
+// iterates over interleaved sample points, decoding them as it goes. stops
+// at num_sample_frames or the last whole block of sound data, whichever
+// comes first
+pub struct Samples<'a, T> {
+    decoder: SoundDecoder,
+    data: &'a [u8],
+    len: usize, // sample points left
+    sample_type: PhantomData<T>,
+}
+
+impl<'a, T: SampleType> Samples<'a, T> {
+    fn new(
+        decoder: SoundDecoder,
+        data: &'a [u8],
+        sample_points: usize,
+    ) -> Samples<'a, T> {
+        let decodable = data.len() / decoder.block_size()
+            * decoder.frames_per_block()
+            * decoder.num_channels;
+        Samples {
+            decoder,
+            data,
+            len: sample_points.min(decodable),
+            sample_type: PhantomData,
+        }
+    }
+}
+
+impl<T: SampleType> Iterator for Samples<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        self.decoder.next_point(&mut self.data)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T: SampleType> ExactSizeIterator for Samples<'_, T> {}
+
+// interleaved sample points viewed as frames, each a slice holding the
+// point for every channel in order
+#[derive(Debug, Clone)]
+pub struct Frames<T> {
+    samples: Vec<T>,
+    num_channels: usize,
+}
+
+impl<T> Frames<T> {
+    pub fn num_channels(&self) -> usize {
+        self.num_channels
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len() / self.num_channels
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn get(&self, frame: usize) -> Option<&[T]> {
+        let start = frame.checked_mul(self.num_channels)?;
+        self.samples.get(start..start + self.num_channels)
+    }
+
+    pub fn iter(&self) -> ChunksExact<'_, T> {
+        self.samples.chunks_exact(self.num_channels)
+    }
+
+    pub fn iter_mut(&mut self) -> ChunksExactMut<'_, T> {
+        self.samples.chunks_exact_mut(self.num_channels)
+    }
+
+    // back to the flat interleaved sample points
+    pub fn into_samples(self) -> Vec<T> {
+        self.samples
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Frames<T> {
+    type Item = &'a [T];
+    type IntoIter = ChunksExact<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut Frames<T> {
+    type Item = &'a mut [T];
+    type IntoIter = ChunksExactMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+// turns SSND data into sample points, holding on to any codec state
+// between calls
+struct SoundDecoder {
+    compression_type: ChunkID,
+    num_channels: usize,
+    bit_rate: i16,
+    packets: Option<PacketDecoder>,
+    // the rest of the last packet decoded, for packet based types
+    pending: VecDeque<i16>,
+}
+
+// the packet based types, which all decode to 16 bit samples
+enum PacketDecoder {
+    Ima4,
+    Mace(mace::Decoder),
+    Gsm(Box<gsm::Decoder>),
+}
 
-        let mut samples = Vec::with_capacity(sample_points);
-        let mut bytes_per_point = (c.bit_rate / 8) as usize;
-        if c.bit_rate % 8 != 0 {
-            bytes_per_point += 1;
+impl PacketDecoder {
+    fn new(id: &ChunkID, num_channels: usize) -> Option<PacketDecoder> {
+        match id {
+            ids::IMA4 => Some(PacketDecoder::Ima4),
+            ids::MAC3 => {
+                Some(PacketDecoder::Mace(mace::Decoder::mace3(num_channels)))
+            }
+            ids::MAC6 => {
+                Some(PacketDecoder::Mace(mace::Decoder::mace6(num_channels)))
+            }
+            ids::GSM => Some(PacketDecoder::Gsm(Box::default())),
+            _ => None,
         }
+    }
+}
 
-        for point in 0..sample_points {
-            samples.push(T::parse(&s.sound_data, point * bytes_per_point, c.bit_rate));
+impl SoundDecoder {
+    fn new(c: &CommonChunk) -> Result<SoundDecoder, Error> {
+        // GSM is mono only
+        if c.num_channels < 1
+            || (&c.compression_type == ids::GSM && c.num_channels != 1)
+        {
+            return Err(Error::InvalidData("number of channels"));
+        }
+        match &c.compression_type {
+            ids::NONE | ids::TWOS | ids::SOWT => {
+                if c.bit_rate < 1 || c.bit_rate > 32 {
+                    return Err(Error::UnsupportedBitRate(c.bit_rate));
+                }
+            }
+            ids::FL32 | ids::FL32_UPPER | ids::FL64 | ids::FL64_UPPER => {}
+            ids::ULAW | ids::ULAW_UPPER | ids::ALAW | ids::ALAW_UPPER => {}
+            ids::IMA4 | ids::MAC3 | ids::MAC6 | ids::GSM => {}
+            id => return Err(Error::UnsupportedCompression(*id)),
         }
+        let num_channels = c.num_channels as usize;
+        Ok(SoundDecoder {
+            compression_type: c.compression_type,
+            num_channels,
+            bit_rate: c.bit_rate,
+            packets: PacketDecoder::new(&c.compression_type, num_channels),
+            pending: VecDeque::new(),
+        })
+    }
+
+    // errors if T is too narrow for the decoded sample points
+    fn check_sample_type<T: SampleType>(&self) -> Result<(), Error> {
+        let bit_width = match &self.compression_type {
+            ids::NONE | ids::TWOS | ids::SOWT => self.bit_rate,
+            ids::FL32 | ids::FL32_UPPER | ids::FL64 | ids::FL64_UPPER => 0,
+            _ => 16,
+        };
+        if bit_width > T::MAX_BIT_WIDTH {
+            return Err(Error::SampleTypeTooSmall(bit_width));
+        }
+        Ok(())
+    }
+
+    // back to the state at the start of the sound data
+    fn reset(&mut self) {
+        self.packets =
+            PacketDecoder::new(&self.compression_type, self.num_channels);
+        self.pending.clear();
+    }
 
-        samples
+    // whether decoding a block depends on the ones before it
+    fn is_stateful(&self) -> bool {
+        matches!(
+            self.packets,
+            Some(PacketDecoder::Mace(_)) | Some(PacketDecoder::Gsm(_))
+        )
     }
 
-    // TODO create samples iterator for better performance
+    // bytes in the smallest piece of sound data that decodes on its own,
+    // which holds `codecs::frames_per_packet` frames
+    fn block_size(&self) -> usize {
+        let ch = self.num_channels;
+        match &self.compression_type {
+            ids::NONE | ids::TWOS | ids::SOWT => {
+                samples::bytes_per_point(self.bit_rate) * ch
+            }
+            ids::FL32 | ids::FL32_UPPER => 4 * ch,
+            ids::FL64 | ids::FL64_UPPER => 8 * ch,
+            ids::ULAW | ids::ULAW_UPPER | ids::ALAW | ids::ALAW_UPPER => ch,
+            ids::IMA4 => ima4::PACKET_SIZE * ch,
+            ids::MAC3 => mace::MAC3_PACKET_SIZE * ch,
+            ids::MAC6 => mace::MAC6_PACKET_SIZE * ch,
+            // GSM is mono only
+            ids::GSM => gsm::PACKET_SIZE,
+            // rejected by SoundDecoder::new
+            _ => unreachable!(),
+        }
+    }
+
+    fn frames_per_block(&self) -> usize {
+        codecs::frames_per_packet(&self.compression_type) as usize
+    }
+
+    // bytes per sample point, for types that aren't packet based
+    fn point_size(&self) -> usize {
+        self.block_size() / self.num_channels
+    }
+
+    // the next sample point, taking a block off the front of `data` when
+    // nothing is left of the last one. None once `data` runs out of blocks
+    fn next_point<T: SampleType>(&mut self, data: &mut &[u8]) -> Option<T> {
+        if self.packets.is_none() {
+            let point = split_front(data, self.point_size())?;
+            return Some(self.decode_point(point));
+        }
+
+        if self.pending.is_empty() {
+            let packets = split_front(data, self.block_size())?;
+            let decoded = self.decode_packets(packets);
+            self.pending.extend(decoded);
+        }
+        self.pending.pop_front().map(samples::from_i16)
+    }
+
+    // decodes a single sample point, for types that aren't packet based
+    fn decode_point<T: SampleType>(&self, point: &[u8]) -> T {
+        match &self.compression_type {
+            ids::SOWT => {
+                let mut swapped = [0; 4];
+                swapped[..point.len()].copy_from_slice(point);
+                swapped[..point.len()].reverse();
+                T::parse(&swapped, 0, self.bit_rate)
+            }
+            ids::FL32 | ids::FL32_UPPER => {
+                let sample = f32::from_be_bytes(point.try_into().unwrap());
+                T::from_f64(sample as f64)
+            }
+            ids::FL64 | ids::FL64_UPPER => {
+                T::from_f64(f64::from_be_bytes(point.try_into().unwrap()))
+            }
+            ids::ULAW | ids::ULAW_UPPER => {
+                samples::from_i16(g711::ulaw_to_linear(point[0]))
+            }
+            ids::ALAW | ids::ALAW_UPPER => {
+                samples::from_i16(g711::alaw_to_linear(point[0]))
+            }
+            _ => T::parse(point, 0, self.bit_rate),
+        }
+    }
+
+    // to interleaved 16 bit samples
+    fn decode_packets(&mut self, data: &[u8]) -> Vec<i16> {
+        match &mut self.packets {
+            Some(PacketDecoder::Ima4) => ima4::decode(data, self.num_channels),
+            Some(PacketDecoder::Mace(decoder)) => decoder.decode(data),
+            Some(PacketDecoder::Gsm(decoder)) => decoder.decode(data),
+            None => panic!("not a packet based compression type"),
+        }
+    }
+}
+
+// where a lazy reader is up to in the sound data
+struct SoundStream {
+    decoder: SoundDecoder,
+    start: u64, // of the first block in the source
+    pos: u64,   // of the next block in the source
+    end: u64,
+    num_frames: u64,
+    frames_left: u64,
+}
+
+impl SoundStream {
+    fn new(
+        c: &CommonChunk,
+        s: &SoundDataChunk,
+        sound_range: Range<u64>,
+    ) -> Result<SoundStream, Error> {
+        let len = sound_range.end - sound_range.start;
+        let start = sound_range.start + (s.offset as u64).min(len);
+        Ok(SoundStream {
+            decoder: SoundDecoder::new(c)?,
+            start,
+            pos: start,
+            end: sound_range.end,
+            num_frames: c.frame_count(),
+            frames_left: c.frame_count(),
+        })
+    }
+
+    // of the next frame to be read
+    fn frame(&self) -> u64 {
+        self.num_frames - self.frames_left
+    }
+
+    fn seek(
+        &mut self,
+        buf: &mut BufReader<impl Read + Seek>,
+        frame: u64,
+    ) -> io::Result<u64> {
+        let frame = frame.min(self.num_frames);
+        if !self.decoder.is_stateful() {
+            // jump to the block holding the frame
+            let frames_per_block = self.decoder.frames_per_block() as u64;
+            let block = frame / frames_per_block;
+            let block_pos =
+                self.start + block * self.decoder.block_size() as u64;
+            self.pos = block_pos.min(self.end);
+            self.frames_left = self.num_frames - block * frames_per_block;
+            self.decoder.reset();
+        } else if frame < self.frame() {
+            self.decoder.reset();
+            self.pos = self.start;
+            self.frames_left = self.num_frames;
+        }
+
+        // decode the rest of the way, these are all 16 bit packet types
+        if self.frame() >= frame {
+            return Ok(self.frame());
+        }
+        let num_channels = self.decoder.num_channels;
+        let len = ((frame - self.frame()) as usize).min(4096);
+        let mut skipped = vec![0i16; len * num_channels];
+        while self.frame() < frame {
+            let frames = ((frame - self.frame()) as usize).min(4096);
+            if self.read(buf, &mut skipped[..frames * num_channels])? == 0 {
+                break;
+            }
+        }
+
+        Ok(self.frame())
+    }
+
+    fn read<T: SampleType>(
+        &mut self,
+        buf: &mut BufReader<impl Read + Seek>,
+        samples: &mut [T],
+    ) -> io::Result<usize> {
+        let num_channels = self.decoder.num_channels;
+        let frames =
+            ((samples.len() / num_channels) as u64).min(self.frames_left);
+        let wanted = frames as usize * num_channels;
+
+        // whole blocks for the points not left over from the last read
+        let pending = self.decoder.pending.len().min(wanted);
+        let block_size = self.decoder.block_size();
+        let blocks = ((wanted - pending) / num_channels)
+            .div_ceil(self.decoder.frames_per_block())
+            .min(((self.end - self.pos) / block_size as u64) as usize);
+
+        let mut data = vec![0; blocks * block_size];
+        if blocks > 0 {
+            buf.seek(SeekFrom::Start(self.pos))?;
+            buf.read_exact(&mut data)?;
+            self.pos += data.len() as u64;
+        }
+
+        let mut data = &data[..];
+        let mut filled = 0;
+        while filled < wanted {
+            match self.decoder.next_point(&mut data) {
+                Some(point) => samples[filled] = point,
+                None => break,
+            }
+            filled += 1;
+        }
+
+        let frames = filled / num_channels;
+        self.frames_left -= frames as u64;
+        Ok(frames)
+    }
+}
+
+// splits `n` bytes off the front of `data`, if it holds that many
+fn split_front<'a>(data: &mut &'a [u8], n: usize) -> Option<&'a [u8]> {
+    if data.len() < n {
+        return None;
+    }
+    let (front, rest) = data.split_at(n);
+    *data = rest;
+    Some(front)
 }
 
 // enums are always the max possible size, so neeeds to be structs and traits
 
-// TODO remove panics
 // TODO move these into their own file - what's a good name?
 
-pub fn read_chunk_id(r: &mut impl Read) -> ids::ChunkID {
+pub fn read_chunk_id(r: &mut impl Read) -> io::Result<ids::ChunkID> {
     let mut id = [0; 4];
-    if let Err(e) = r.read_exact(&mut id) {
-        panic!("unable to read_u8 {:?}", e)
-    }
-    id
+    r.read_exact(&mut id)?;
+    Ok(id)
 }
 
-pub fn read_u8(r: &mut impl Read) -> u8 {
+pub fn read_u8(r: &mut impl Read) -> io::Result<u8> {
     let mut b = [0; 1];
-    if let Err(e) = r.read_exact(&mut b) {
-        panic!("unable to read_u8 {:?}", e)
-    }
-    b[0]
+    r.read_exact(&mut b)?;
+    Ok(b[0])
 }
 
-pub fn read_u16_be(r: &mut impl Read) -> u16 {
+pub fn read_u16_be(r: &mut impl Read) -> io::Result<u16> {
     let mut b = [0; 2];
-    if let Err(e) = r.read_exact(&mut b) {
-        panic!("unable to read_u8 {:?}", e)
-    }
-    u16::from_be_bytes(b)
+    r.read_exact(&mut b)?;
+    Ok(u16::from_be_bytes(b))
 }
 
-pub fn read_u32_be(r: &mut impl Read) -> u32 {
+pub fn read_u32_be(r: &mut impl Read) -> io::Result<u32> {
     let mut b = [0; 4];
-    if let Err(e) = r.read_exact(&mut b) {
-        panic!("unable to read_i32_be {:?}", e)
-    }
-    u32::from_be_bytes(b)
+    r.read_exact(&mut b)?;
+    Ok(u32::from_be_bytes(b))
 }
 
-pub fn read_i8_be(r: &mut impl Read) -> i8 {
+pub fn read_i8_be(r: &mut impl Read) -> io::Result<i8> {
     let mut b = [0; 1];
-    if let Err(e) = r.read_exact(&mut b) {
-        panic!("unable to read_i32_be {:?}", e)
-    }
-    i8::from_be_bytes(b)
+    r.read_exact(&mut b)?;
+    Ok(i8::from_be_bytes(b))
 }
 
-pub fn read_i16_be(r: &mut impl Read) -> i16 {
+pub fn read_i16_be(r: &mut impl Read) -> io::Result<i16> {
     let mut b = [0; 2];
-    if let Err(e) = r.read_exact(&mut b) {
-        panic!("unable to read_i32_be {:?}", e)
-    }
-    i16::from_be_bytes(b)
+    r.read_exact(&mut b)?;
+    Ok(i16::from_be_bytes(b))
 }
 
-pub fn read_i32_be(r: &mut impl Read) -> i32 {
+pub fn read_i32_be(r: &mut impl Read) -> io::Result<i32> {
     let mut b = [0; 4];
-    if let Err(e) = r.read_exact(&mut b) {
-        panic!("unable to read_i32_be {:?}", e)
+    r.read_exact(&mut b)?;
+    Ok(i32::from_be_bytes(b))
+}
+
+// reads len bytes without allocating them all up front, so a corrupt chunk
+// size fails with an io error instead of a huge allocation
+pub fn read_bytes<R: Read>(r: &mut R, len: usize) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    r.take(len as u64).read_to_end(&mut data)?;
+    if data.len() < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
     }
-    i32::from_be_bytes(b)
+    Ok(data)
 }
 
 // TODO testme with pascal strings
-pub fn read_pstring<R: Read + Seek>(r: &mut R) -> String {
-    let len = read_u8(r);
+pub fn read_pstring<R: Read + Seek>(r: &mut R) -> io::Result<String> {
+    let len = read_u8(r)?;
     let mut str_buf = vec![0; len as usize];
-    r.read_exact(&mut str_buf).unwrap();
+    r.read_exact(&mut str_buf)?;
+
+    if len.is_multiple_of(2) {
+        // skip pad byte if the count byte + text is odd
+        r.seek(SeekFrom::Current(1))?;
+    }
+
+    // not necessarily utf8, eg the mac roman 'µ' in "µLaw 2:1"
+    Ok(String::from_utf8_lossy(&str_buf).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extended::encode_extended_precision_bytes;
+    use crate::writer::{self, AiffSpec, AiffWriter};
+    use std::io::{Cursor, Write};
+
+    // 3 mono 8 bit frames, so the SSND chunk has a pad byte. the FORM size
+    // is at byte 4 and the pad byte is the last one
+    fn write(compression_type: &ChunkID) -> Vec<u8> {
+        let spec = AiffSpec {
+            num_channels: 1,
+            bit_rate: if compression_type == ids::NONE { 8 } else { 16 },
+            sample_rate: 8000f64,
+            compression_type: *compression_type,
+            block_size: 0,
+        };
+        let mut writer = AiffWriter::new(Vec::new(), spec);
+        writer.write(&[1i16, 2, 3]).unwrap();
+        writer.into_inner()
+    }
+
+    fn form_size(data: &[u8]) -> i32 {
+        i32::from_be_bytes(data[4..8].try_into().unwrap())
+    }
+
+    fn set_form_size(data: &mut [u8], size: i32) {
+        data[4..8].copy_from_slice(&size.to_be_bytes());
+    }
+
+    // lenient readers record warnings starting with `expected`, in order,
+    // and still read the samples. strict readers fail on the first
+    fn check(data: Vec<u8>, expected: &[&str]) {
+        let mut reader = AiffReader::new(Cursor::new(data.clone()));
+        reader.read().unwrap();
+        let warnings = reader.warnings();
+        assert_eq!(warnings.len(), expected.len(), "{:?}", warnings);
+        for (warning, expected) in warnings.iter().zip(expected) {
+            assert!(warning.description.starts_with(expected), "{}", warning);
+        }
+        assert_eq!(reader.samples::<i8>().unwrap(), [1, 2, 3]);
+
+        let options = ReadOptions {
+            strict: true,
+            ..ReadOptions::default()
+        };
+        let mut reader = AiffReader::with_options(Cursor::new(data), options);
+        match (reader.read(), expected.first()) {
+            (Ok(()), None) => {}
+            (Err(Error::SpecViolation(w)), Some(expected)) => {
+                assert!(w.description.starts_with(expected), "{}", w)
+            }
+            (result, _) => panic!("unexpected strict result {:?}", result),
+        }
+    }
 
-    if len % 2 > 0 {
-        // skip pad byte if odd
-        r.seek(SeekFrom::Current(1)).unwrap();
+    #[test]
+    fn reads_writer_output_cleanly() {
+        check(write(ids::NONE), &[]);
     }
 
-    String::from_utf8(str_buf).unwrap()
+    #[test]
+    fn recovers_from_a_short_form_size() {
+        // only covering the COMM chunk
+        let mut data = write(ids::NONE);
+        set_form_size(&mut data, 4 + 8 + 18);
+        check(data, &["FORM size 30 doesn't cover its chunks"]);
+    }
+
+    #[test]
+    fn recovers_from_a_long_form_size() {
+        let mut data = write(ids::NONE);
+        let size = form_size(&data) + 10;
+        set_form_size(&mut data, size);
+        check(data, &["FORM size 60 runs past the end of the file"]);
+    }
+
+    #[test]
+    fn recovers_from_a_missing_pad_byte() {
+        let mut data = write(ids::NONE);
+        data.pop();
+        let size = form_size(&data) - 1;
+        set_form_size(&mut data, size);
+        check(data, &["missing pad byte"]);
+    }
+
+    #[test]
+    fn recovers_from_truncated_sound_data() {
+        // the pad byte and the last sample point. only the truncation is
+        // reported for the SSND chunk, not the pad byte as well
+        let mut data = write(ids::NONE);
+        data.truncate(data.len() - 2);
+        let mut reader = AiffReader::new(Cursor::new(data));
+        reader.read().unwrap();
+        let warnings: Vec<_> = reader
+            .warnings()
+            .iter()
+            .map(|w| &w.description[..])
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                "sound data runs past the end of the file",
+                "FORM size 50 runs past the end of the file"
+            ]
+        );
+        assert_eq!(reader.samples::<i8>().unwrap(), [1, 2]);
+    }
+
+    #[test]
+    fn recovers_from_trailing_data() {
+        let mut data = write(ids::NONE);
+        data.extend_from_slice(&[1, 2, 3]);
+        check(data, &["3 byte(s) of trailing data"]);
+    }
+
+    #[test]
+    fn skips_an_id3v1_trailer() {
+        let mut data = write(ids::NONE);
+        let mut tag = b"TAGtitle".to_vec();
+        tag.resize(128, 0);
+        data.extend_from_slice(&tag);
+        check(data, &[]);
+    }
+
+    #[test]
+    fn recovers_from_a_bad_id3_chunk() {
+        let mut data = write(ids::NONE);
+        data.extend_from_slice(ids::ID3_CHUNK);
+        data.extend_from_slice(&10i32.to_be_bytes());
+        data.extend_from_slice(&[0; 10]);
+        let size = form_size(&data) + 18;
+        set_form_size(&mut data, size);
+        check(data, &["ID3 tag skipped"]);
+    }
+
+    #[test]
+    fn recovers_from_a_missing_fver_chunk() {
+        let mut data = write(ids::ULAW);
+        assert_eq!(&data[12..16], ids::FVER);
+        data.drain(12..24);
+        let size = form_size(&data) - 12;
+        set_form_size(&mut data, size);
+        let mut reader = AiffReader::new(Cursor::new(data.clone()));
+        reader.read().unwrap();
+        let warnings: Vec<_> = reader
+            .warnings()
+            .iter()
+            .map(|w| &w.description[..])
+            .collect();
+        assert_eq!(warnings, ["missing FVER chunk"]);
+
+        let options = ReadOptions {
+            strict: true,
+            ..ReadOptions::default()
+        };
+        let mut reader = AiffReader::with_options(Cursor::new(data), options);
+        assert!(matches!(reader.read(), Err(Error::SpecViolation(_))));
+    }
+
+    fn write_frames(spec: AiffSpec, num_frames: usize) -> Vec<u8> {
+        let samples: Vec<i16> = (0..num_frames * spec.num_channels as usize)
+            .map(|i| ((i * 7919) % 65536) as i16)
+            .collect();
+        let mut writer = AiffWriter::new(Vec::new(), spec);
+        writer.write(&samples).unwrap();
+        writer.into_inner()
+    }
+
+    // the writer doesn't encode MACE, so this holds the packets as given
+    fn write_packets(
+        num_channels: i16,
+        compression_type: &ChunkID,
+        num_packets: u32,
+        packets: &[u8],
+    ) -> Vec<u8> {
+        let mut w = Vec::new();
+        writer::write_chunk_id(&mut w, ids::FORM).unwrap();
+        writer::write_i32_be(&mut w, 4 + 12 + 32 + 16 + packets.len() as i32)
+            .unwrap();
+        writer::write_chunk_id(&mut w, ids::AIFF_C).unwrap();
+        writer::write_chunk_id(&mut w, ids::FVER).unwrap();
+        writer::write_i32_be(&mut w, 4).unwrap();
+        writer::write_u32_be(&mut w, chunks::AIFC_VERSION_1).unwrap();
+        writer::write_chunk_id(&mut w, ids::COMMON).unwrap();
+        writer::write_i32_be(&mut w, 24).unwrap();
+        writer::write_i16_be(&mut w, num_channels).unwrap();
+        writer::write_u32_be(&mut w, num_packets).unwrap();
+        writer::write_i16_be(&mut w, 8).unwrap();
+        w.write_all(&encode_extended_precision_bytes(8000f64))
+            .unwrap();
+        writer::write_chunk_id(&mut w, compression_type).unwrap();
+        writer::write_pstring(&mut w, b"").unwrap();
+        writer::write_chunk_id(&mut w, ids::SOUND).unwrap();
+        writer::write_i32_be(&mut w, 8 + packets.len() as i32).unwrap();
+        writer::write_u32_be(&mut w, 0).unwrap(); // offset
+        writer::write_u32_be(&mut w, 0).unwrap(); // block size
+        w.write_all(packets).unwrap();
+        w
+    }
+
+    fn mace_packets(len: usize) -> Vec<u8> {
+        (0..len).map(|i| ((i * 151 + 17) % 256) as u8).collect()
+    }
+
+    // after each seek, lazy reads give the eagerly read points from there on
+    fn check_seeks(data: Vec<u8>, num_channels: usize) {
+        let mut reader = AiffReader::new(Cursor::new(data.clone()));
+        reader.read().unwrap();
+        let expected = reader.samples::<i16>().unwrap();
+        let num_frames = expected.len() / num_channels;
+        assert!(num_frames > 200);
+
+        let mut reader = AiffReader::new_lazy(Cursor::new(data));
+        reader.read().unwrap();
+        let mut samples = vec![0i16; expected.len()];
+        // forwards, backwards, on and off block boundaries
+        for frame in [100, 7, 130, 129, 64, 0, num_frames - 1, 3] {
+            assert_eq!(reader.seek_frame(frame as u64).unwrap(), frame as u64);
+            let read = reader.read_frames(&mut samples).unwrap();
+            assert_eq!(read, num_frames - frame);
+            assert_eq!(
+                &samples[..read * num_channels],
+                &expected[frame * num_channels..]
+            );
+        }
+
+        // 8000 Hz
+        assert_eq!(reader.seek_time(Duration::from_millis(20)).unwrap(), 160);
+        let read = reader.read_frames(&mut samples[..num_channels]).unwrap();
+        assert_eq!(read, 1);
+        assert_eq!(
+            &samples[..num_channels],
+            &expected[160 * num_channels..161 * num_channels]
+        );
+
+        let end = num_frames as u64;
+        assert_eq!(reader.seek_frame(u64::MAX).unwrap(), end);
+        assert_eq!(reader.read_frames(&mut samples).unwrap(), 0);
+    }
+
+    #[test]
+    fn seeks_pcm() {
+        let spec = AiffSpec {
+            num_channels: 2,
+            bit_rate: 16,
+            sample_rate: 8000f64,
+            compression_type: *ids::NONE,
+            block_size: 0,
+        };
+        check_seeks(write_frames(spec, 300), 2);
+    }
+
+    #[test]
+    fn seeks_ima4() {
+        let spec = AiffSpec {
+            num_channels: 2,
+            bit_rate: 16,
+            sample_rate: 8000f64,
+            compression_type: *ids::IMA4,
+            block_size: 0,
+        };
+        check_seeks(write_frames(spec, 300), 2);
+    }
+
+    #[test]
+    fn seeks_mace() {
+        // 2 bytes per channel per 6 frame packet
+        let data = write_packets(2, ids::MAC3, 50, &mace_packets(200));
+        check_seeks(data, 2);
+    }
+
+    // lazy reads into buffers of any size give the eagerly read points
+    fn check_lazy_reads(data: Vec<u8>, num_channels: usize) {
+        let mut reader = AiffReader::new(Cursor::new(data.clone()));
+        reader.read().unwrap();
+        let expected = reader.samples::<i16>().unwrap();
+
+        // one frame, a few frames and a point over, and everything at once.
+        // decoded blocks that don't fit are left over for the next call
+        let lens = [num_channels, 7 * num_channels + 1, expected.len() + 5];
+        for len in lens.iter() {
+            let mut reader = AiffReader::new_lazy(Cursor::new(data.clone()));
+            reader.read().unwrap();
+            let mut buf = vec![0i16; *len];
+            let mut samples = Vec::new();
+            loop {
+                let read = reader.read_frames(&mut buf).unwrap();
+                if read == 0 {
+                    break;
+                }
+                samples.extend_from_slice(&buf[..read * num_channels]);
+            }
+            assert_eq!(samples, expected, "buffer of {} points", len);
+        }
+
+        let mut reader = AiffReader::new_lazy(Cursor::new(data));
+        reader.read().unwrap();
+        let mut buf = vec![0i16; num_channels - 1];
+        assert!(matches!(reader.read_frames(&mut buf), Err(Error::Io(_))));
+    }
+
+    fn sound_offset(data: &[u8]) -> u32 {
+        let mut reader = AiffReader::new(Cursor::new(data));
+        reader.read().unwrap();
+        let form = reader.form().as_ref().unwrap();
+        form.sound().as_ref().unwrap().offset
+    }
+
+    #[test]
+    fn reads_pcm_lazily() {
+        let spec = AiffSpec {
+            num_channels: 2,
+            bit_rate: 16,
+            sample_rate: 8000f64,
+            compression_type: *ids::NONE,
+            block_size: 16,
+        };
+        let data = write_frames(spec, 300);
+        assert!(sound_offset(&data) > 0);
+        check_lazy_reads(data, 2);
+    }
+
+    #[test]
+    fn reads_ima4_lazily() {
+        let spec = AiffSpec {
+            num_channels: 2,
+            bit_rate: 16,
+            sample_rate: 8000f64,
+            compression_type: *ids::IMA4,
+            block_size: 16,
+        };
+        let data = write_frames(spec, 300);
+        assert!(sound_offset(&data) > 0);
+        check_lazy_reads(data, 2);
+    }
+
+    #[test]
+    fn reads_mace_lazily() {
+        let data = write_packets(2, ids::MAC3, 50, &mace_packets(200));
+        check_lazy_reads(data, 2);
+    }
 }
@@ -1,150 +1,147 @@
-pub struct AiffSamples {
-    pos: usize,
-}
-
 pub trait SampleType: Sized {
+    // widest sample points the type takes. integer types hold them without
+    // losing bits, float types take any width and normalize it
+    const MAX_BIT_WIDTH: i16;
+
     fn parse(data: &[u8], pos: usize, bit_width: i16) -> Self;
+    fn encode(&self, buf: &mut Vec<u8>, bit_width: i16);
+    // for float payloads, which are expected to be in the range [-1.0, 1.0]
+    fn from_f64(sample: f64) -> Self;
 }
 
-// TODO handle padding for non standard bit rates
-// TODO handle offset + blocksize parameters
-// FIXME proper error handling
+// sample points are stored in the fewest whole bytes that fit bit_width
+pub fn bytes_per_point(bit_width: i16) -> usize {
+    (bit_width as usize).div_ceil(8)
+}
 
-impl SampleType for i8 {
-    fn parse(data: &[u8], pos: usize, bit_width: i16) -> Self {
-        if bit_width < 8 {
-            unimplemented!("only 16 24 32 bit supported, got {:?}", bit_width)
-        } else if bit_width > 8 {
-            panic!("invalid bit width supplied. expected 8 vs {:?}", bit_width)
-        }
-        i8::from_be_bytes([data[pos]])
-    }
+// output of the AIFF-C codecs, treated as 16 bit sample points
+pub fn from_i16<T: SampleType>(sample: i16) -> T {
+    T::parse(&sample.to_be_bytes(), 0, 16)
 }
 
-impl SampleType for i16 {
-    fn parse(data: &[u8], pos: usize, bit_width: i16) -> Self {
-        if bit_width < 16 {
-            unimplemented!("only 16 24 32 bit supported, got {:?}", bit_width)
-        } else if bit_width > 16 {
-            panic!("invalid bit width supplied. expected 16 vs {:?}", bit_width)
-        }
-        i16::from_be_bytes([data[pos], data[pos + 1]])
+// sample points are left justified, with the unused low order bits zeroed.
+// reads one into the low order bits of an i32, sign extending it
+fn read_point(data: &[u8], pos: usize, bit_width: i16) -> i32 {
+    let mut val = 0u32;
+    for (idx, byte) in data[pos..pos + bytes_per_point(bit_width)]
+        .iter()
+        .enumerate()
+    {
+        val |= (*byte as u32) << (24 - idx * 8);
     }
+    (val as i32) >> (32 - bit_width)
 }
 
-impl SampleType for i32 {
-    fn parse(data: &[u8], pos: usize, bit_width: i16) -> Self {
-        match bit_width {
-            32 => i32::from_be_bytes([
-                data[pos],
-                data[pos + 1],
-                data[pos + 2],
-                data[pos + 3],
-            ]),
-            24 => {
-                let is_pos = data[pos] & 0b1000_0000 == 0;
-                let extra_byte;
-                if is_pos {
-                    extra_byte = u8::MIN;
-                } else {
-                    extra_byte = u8::MAX;
-                }
-                i32::from_be_bytes([extra_byte, data[pos], data[pos + 1], data[pos + 2]])
-            }
-            b if b <= 16 => panic!("invalid bit width supplied. expected 24 or 32 vs {:?}", b),
-            b => unimplemented!("only 16 24 32 bit supported, got {:?}", b),
-        }
+// the inverse of read_point, clipping values out of range for bit_width
+fn write_point(buf: &mut Vec<u8>, val: i32, bit_width: i16) {
+    let max = (1i64 << (bit_width - 1)) - 1;
+    let val = (val as i64).clamp(-max - 1, max) as i32;
+    let val = ((val as u32) << (32 - bit_width)).to_be_bytes();
+    buf.extend_from_slice(&val[..bytes_per_point(bit_width)]);
+}
+
+// a type can hold any bit width up to its own size
+fn check_bit_width(bit_width: i16, max: i16) {
+    if bit_width < 1 || bit_width > max {
+        panic!(
+            "invalid bit width supplied. expected 1 to {:?} vs {:?}",
+            max, bit_width
+        )
     }
 }
 
-// impl SampleType for f32 {
-//     fn parse(data: &[u8], pos: usize, bit_width: i16) -> Self {
-//         let int_val = i32::parse(data, pos, bit_width);
-//         int_val as f32
-//     }
-// }
+impl SampleType for i8 {
+    const MAX_BIT_WIDTH: i16 = 8;
+
+    fn parse(data: &[u8], pos: usize, bit_width: i16) -> Self {
+        check_bit_width(bit_width, Self::MAX_BIT_WIDTH);
+        read_point(data, pos, bit_width) as i8
+    }
 
-// I made this before deciding not to implement an iterator. so this will
-// probably need to be refactored when iterator is implemented
+    fn encode(&self, buf: &mut Vec<u8>, bit_width: i16) {
+        check_bit_width(bit_width, Self::MAX_BIT_WIDTH);
+        write_point(buf, *self as i32, bit_width);
+    }
 
-pub struct Samples8 {
-    point: usize,
-    sound_data: Vec<u8>,
+    fn from_f64(sample: f64) -> Self {
+        denormalize(sample, 8) as i8
+    }
 }
 
-impl Iterator for Samples8 {
-    type Item = i8;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        let target = self.point * 4;
-        if (target + 2) > self.sound_data.len() {
-            // out of bounds, panic?
-            return None;
-        }
+impl SampleType for i16 {
+    const MAX_BIT_WIDTH: i16 = 16;
 
-        self.point += 1;
+    fn parse(data: &[u8], pos: usize, bit_width: i16) -> Self {
+        check_bit_width(bit_width, Self::MAX_BIT_WIDTH);
+        read_point(data, pos, bit_width) as i16
+    }
 
-        Some(i8::from_be_bytes([self.sound_data[target]]))
+    fn encode(&self, buf: &mut Vec<u8>, bit_width: i16) {
+        check_bit_width(bit_width, Self::MAX_BIT_WIDTH);
+        write_point(buf, *self as i32, bit_width);
     }
-}
 
-pub struct Samples16 {
-    point: usize,
-    sound_data: Vec<u8>,
+    fn from_f64(sample: f64) -> Self {
+        denormalize(sample, 16) as i16
+    }
 }
 
-impl Iterator for Samples16 {
-    type Item = i16;
+impl SampleType for i32 {
+    const MAX_BIT_WIDTH: i16 = 32;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let target = self.point * 4;
-        if (target + 2) > self.sound_data.len() {
-            // out of bounds, panic?
-            return None;
-        }
+    fn parse(data: &[u8], pos: usize, bit_width: i16) -> Self {
+        check_bit_width(bit_width, Self::MAX_BIT_WIDTH);
+        read_point(data, pos, bit_width)
+    }
 
-        self.point += 1;
+    fn encode(&self, buf: &mut Vec<u8>, bit_width: i16) {
+        check_bit_width(bit_width, Self::MAX_BIT_WIDTH);
+        write_point(buf, *self, bit_width);
+    }
 
-        Some(i16::from_be_bytes([
-            self.sound_data[target],
-            self.sound_data[target + 1],
-        ]))
+    fn from_f64(sample: f64) -> Self {
+        denormalize(sample, 32)
     }
 }
 
-pub struct Samples32 {
-    point: usize,
-    sound_data: Vec<u8>,
-}
+// integer payloads are normalized to [-1.0, 1.0] based on the bit width.
+// points over 24 bits lose their low bits to the mantissa
+impl SampleType for f32 {
+    const MAX_BIT_WIDTH: i16 = 32;
 
-// generic iterator possible?
-impl Samples32 {
-    pub fn new(sound_data: Vec<u8>) -> Self {
-        // TODO should uneven size (relative to points or frames) panic?
-        Samples32 {
-            point: 0,
-            sound_data,
-        }
+    fn parse(data: &[u8], pos: usize, bit_width: i16) -> Self {
+        f64::parse(data, pos, bit_width) as f32
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>, bit_width: i16) {
+        (*self as f64).encode(buf, bit_width)
+    }
+
+    fn from_f64(sample: f64) -> Self {
+        sample as f32
     }
 }
 
-impl Iterator for Samples32 {
-    type Item = i32;
+impl SampleType for f64 {
+    const MAX_BIT_WIDTH: i16 = 32;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let target = self.point * 4;
-        if (target + 3) > self.sound_data.len() {
-            // out of bounds, panic?
-            return None;
-        }
+    fn parse(data: &[u8], pos: usize, bit_width: i16) -> Self {
+        i32::parse(data, pos, bit_width) as f64
+            / 2f64.powi(bit_width as i32 - 1)
+    }
 
-        self.point += 1;
+    fn encode(&self, buf: &mut Vec<u8>, bit_width: i16) {
+        denormalize(*self, bit_width).encode(buf, bit_width)
+    }
 
-        Some(i32::from_be_bytes([
-            self.sound_data[target],
-            self.sound_data[target + 1],
-            self.sound_data[target + 2],
-            self.sound_data[target + 3],
-        ]))
+    fn from_f64(sample: f64) -> Self {
+        sample
     }
 }
+
+// scales a float in [-1.0, 1.0] to a signed integer of bit_width bits,
+// clipping anything out of range
+fn denormalize(sample: f64, bit_width: i16) -> i32 {
+    let max = 2f64.powi(bit_width as i32 - 1);
+    (sample * max).round().max(-max).min(max - 1f64) as i32
+}
@@ -0,0 +1,610 @@
+use super::{
+    chunks::{AIFC_VERSION_1, AIFF_COMMON_SIZE},
+    codecs::{self, g711, ima4},
+    extended::encode_extended_precision_bytes,
+    ids::{self, ChunkID},
+    samples::{self, SampleType},
+};
+use std::convert::TryFrom;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+const FORMAT_VERSION_SIZE: i32 = 4;
+const SOUND_HEADER_SIZE: i32 = 8; // offset + block size
+
+// byte offset of the FORM size from the start of the FORM chunk
+const FORM_SIZE_POS: u64 = 4;
+
+// the CommonChunk fields a writer needs up front. frame count and chunk
+// sizes are derived from the samples. any compression type other than
+// NONE produces an AIFF-C file. a non zero block_size aligns the start of
+// the sound data to a multiple of it, as a position in the sink
+#[derive(Debug, Clone, Copy)]
+pub struct AiffSpec {
+    pub num_channels: i16,
+    pub bit_rate: i16, // in the spec, this is defined as `sample_size`
+    pub sample_rate: f64,
+    pub compression_type: ChunkID,
+    pub block_size: u32,
+}
+
+impl AiffSpec {
+    fn validate(&self) -> io::Result<()> {
+        if self.num_channels < 1 {
+            return Err(invalid_input("num_channels must be at least 1"));
+        }
+        if self.bit_rate < 1 || self.bit_rate > 32 {
+            return Err(invalid_input("bit_rate must be between 1 and 32"));
+        }
+        if !self.sample_rate.is_normal() || self.sample_rate < 0f64 {
+            return Err(invalid_input("sample_rate must be positive"));
+        }
+        if codecs::compression_name(&self.compression_type).is_none() {
+            return Err(invalid_input("compression type can't be written"));
+        }
+        if self.is_aiff_c() && self.bit_rate != 16 {
+            return Err(invalid_input("compressed bit_rate must be 16"));
+        }
+        Ok(())
+    }
+
+    fn is_aiff_c(&self) -> bool {
+        &self.compression_type != ids::NONE
+    }
+
+    fn bytes_per_point(&self) -> usize {
+        samples::bytes_per_point(self.bit_rate)
+    }
+
+    fn compression_name(&self) -> &'static [u8] {
+        codecs::compression_name(&self.compression_type).unwrap_or(b"")
+    }
+
+    fn common_size(&self) -> i32 {
+        if self.is_aiff_c() {
+            // compression type + pstring padded to an even length
+            let pstring_len = self.compression_name().len() as i32 + 1;
+            AIFF_COMMON_SIZE + 4 + pstring_len + pstring_len % 2
+        } else {
+            AIFF_COMMON_SIZE
+        }
+    }
+
+    // bytes from the start of the FORM chunk to the COMM chunk
+    fn common_pos(&self) -> u64 {
+        match self.is_aiff_c() {
+            true => 12 + 8 + FORMAT_VERSION_SIZE as u64,
+            false => 12,
+        }
+    }
+
+    fn num_sample_frames_pos(&self) -> u64 {
+        self.common_pos() + 8 + 2
+    }
+
+    fn sound_size_pos(&self) -> u64 {
+        self.common_pos() + 8 + self.common_size() as u64 + 4
+    }
+
+    // SSND offset needed to align the sound data for a FORM chunk starting
+    // at `start`
+    fn sound_offset(&self, start: u64) -> u32 {
+        if self.block_size == 0 {
+            return 0;
+        }
+        let block_size = self.block_size as u64;
+        let data_pos =
+            start + self.sound_size_pos() + 4 + SOUND_HEADER_SIZE as u64;
+        ((block_size - data_pos % block_size) % block_size) as u32
+    }
+
+    // FORM size, which counts the form type and the SSND pad byte
+    fn form_size(&self, sound_size: u64) -> u64 {
+        self.sound_size_pos() + 4 + sound_size + sound_size % 2 - 8
+    }
+
+    // SSND size, which counts the offset + block size fields and the offset
+    // padding but not the pad byte
+    fn sound_size(&self, offset: u32, sound_bytes: u64) -> io::Result<i32> {
+        let size = sound_bytes + offset as u64 + SOUND_HEADER_SIZE as u64;
+        // the FORM size has to fit as well
+        if self.form_size(size) > i32::MAX as u64 {
+            return Err(invalid_input("sound data too large for AIFF"));
+        }
+        Ok(size as i32)
+    }
+}
+
+pub struct AiffWriter<Sink> {
+    sink: Sink,
+    spec: AiffSpec,
+}
+
+impl<Sink: Write> AiffWriter<Sink> {
+    pub fn new(sink: Sink, spec: AiffSpec) -> AiffWriter<Sink> {
+        AiffWriter { sink, spec }
+    }
+
+    pub fn spec(&self) -> &AiffSpec {
+        &self.spec
+    }
+
+    // writes a complete FORM chunk. samples are interleaved sample points,
+    // so the length must be a multiple of num_channels. the sink is assumed
+    // to be at the start of the file for block alignment
+    pub fn write<T: SampleType>(&mut self, samples: &[T]) -> io::Result<()> {
+        check_frames(samples, &self.spec)?;
+        self.write_points(samples.iter())
+    }
+
+    // like `write`, but with one buffer of samples per channel
+    pub fn write_planar<T: SampleType>(
+        &mut self,
+        channels: &[impl AsRef<[T]>],
+    ) -> io::Result<()> {
+        self.write_points(interleave(channels, &self.spec)?)
+    }
+
+    fn write_points<'a, T: SampleType + 'a>(
+        &mut self,
+        samples: impl Iterator<Item = &'a T>,
+    ) -> io::Result<()> {
+        self.spec.validate()?;
+        check_sample_type::<T>(&self.spec)?;
+
+        let mut encoder = SoundEncoder::new(self.spec);
+        let mut sound_data = Vec::new();
+        let frames = encoder.encode(samples, &mut sound_data)
+            + encoder.flush(&mut sound_data);
+        let num_sample_frames = u32::try_from(frames)
+            .map_err(|_| invalid_input("too many sample frames"))?;
+        let offset = self.spec.sound_offset(0);
+        let sound_size =
+            self.spec.sound_size(offset, sound_data.len() as u64)?;
+
+        let w = &mut self.sink;
+        write_header(w, &self.spec, num_sample_frames, sound_size, offset)?;
+        w.write_all(&sound_data)?;
+        if sound_size % 2 > 0 {
+            w.write_all(&[0])?;
+        }
+
+        w.flush()
+    }
+
+    pub fn into_inner(self) -> Sink {
+        self.sink
+    }
+}
+
+// the streaming counterpart to `AiffWriter`. the header is written with
+// placeholder sizes on creation, sample frames can then be written in any
+// number of calls, and the sizes are patched by `finalize` (or on drop)
+pub struct AiffStreamWriter<Sink: Write + Seek> {
+    sink: Option<Sink>,
+    spec: AiffSpec,
+    encoder: SoundEncoder,
+    start: u64, // position of the FORM chunk in the sink
+    offset: u32,
+    num_sample_frames: u32,
+    sound_bytes: u64,
+    finished: bool, // a failed finish isn't retried on drop
+}
+
+impl<Sink: Write + Seek> AiffStreamWriter<Sink> {
+    pub fn new(mut sink: Sink, spec: AiffSpec) -> io::Result<Self> {
+        spec.validate()?;
+
+        let start = sink.stream_position()?;
+        let offset = spec.sound_offset(start);
+        let sound_size = spec.sound_size(offset, 0)?;
+        write_header(&mut sink, &spec, 0, sound_size, offset)?;
+
+        Ok(AiffStreamWriter {
+            sink: Some(sink),
+            spec,
+            encoder: SoundEncoder::new(spec),
+            start,
+            offset,
+            num_sample_frames: 0,
+            sound_bytes: 0,
+            finished: false,
+        })
+    }
+
+    pub fn spec(&self) -> &AiffSpec {
+        &self.spec
+    }
+
+    // as written to the COMM chunk, so packets for packet based types
+    pub fn num_sample_frames(&self) -> u32 {
+        self.num_sample_frames
+    }
+
+    // samples are interleaved sample points and must contain whole frames
+    pub fn write_frames<T: SampleType>(
+        &mut self,
+        samples: &[T],
+    ) -> io::Result<()> {
+        check_frames(samples, &self.spec)?;
+        self.write_points(samples.iter())
+    }
+
+    // like `write_frames`, but with one buffer of samples per channel
+    pub fn write_frames_planar<T: SampleType>(
+        &mut self,
+        channels: &[impl AsRef<[T]>],
+    ) -> io::Result<()> {
+        let samples = interleave(channels, &self.spec)?;
+        self.write_points(samples)
+    }
+
+    fn write_points<'a, T: SampleType + 'a>(
+        &mut self,
+        samples: impl Iterator<Item = &'a T>,
+    ) -> io::Result<()> {
+        check_sample_type::<T>(&self.spec)?;
+        let mut sound_data = Vec::new();
+        let frames = self.encoder.encode(samples, &mut sound_data);
+        self.write_sound_data(&sound_data, frames)
+    }
+
+    fn write_sound_data(&mut self, data: &[u8], frames: u64) -> io::Result<()> {
+        let num_sample_frames =
+            u32::try_from(self.num_sample_frames as u64 + frames)
+                .map_err(|_| invalid_input("too many sample frames"))?;
+        // refuse data that would overflow the FORM or SSND size
+        let sound_bytes = self.sound_bytes + data.len() as u64;
+        self.spec.sound_size(self.offset, sound_bytes)?;
+
+        self.sink_mut()?.write_all(data)?;
+        self.num_sample_frames = num_sample_frames;
+        self.sound_bytes = sound_bytes;
+
+        Ok(())
+    }
+
+    // writes the pad byte, patches the FORM, COMM and SSND sizes and
+    // returns the sink positioned at the end of the FORM chunk
+    pub fn finalize(mut self) -> io::Result<Sink> {
+        self.finish()?;
+        Ok(self.sink.take().expect("sink is only taken on finalize"))
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.finished = true;
+        let mut sound_data = Vec::new();
+        let frames = self.encoder.flush(&mut sound_data);
+        self.write_sound_data(&sound_data, frames)?;
+
+        let (start, frames, spec) =
+            (self.start, self.num_sample_frames, self.spec);
+        let sound_size = spec.sound_size(self.offset, self.sound_bytes)?;
+        let pad = sound_size % 2;
+        let form_size = spec.form_size(sound_size as u64) as i32;
+
+        let w = self.sink_mut()?;
+        if pad > 0 {
+            w.write_all(&[0])?;
+        }
+        let end = w.stream_position()?;
+
+        w.seek(SeekFrom::Start(start + FORM_SIZE_POS))?;
+        write_i32_be(w, form_size)?;
+        w.seek(SeekFrom::Start(start + spec.num_sample_frames_pos()))?;
+        write_u32_be(w, frames)?;
+        w.seek(SeekFrom::Start(start + spec.sound_size_pos()))?;
+        write_i32_be(w, sound_size)?;
+
+        w.seek(SeekFrom::Start(end))?;
+        w.flush()
+    }
+
+    fn sink_mut(&mut self) -> io::Result<&mut Sink> {
+        self.sink
+            .as_mut()
+            .ok_or_else(|| io::Error::other("writer already finalized"))
+    }
+}
+
+impl<Sink: Write + Seek> Drop for AiffStreamWriter<Sink> {
+    fn drop(&mut self) {
+        if !self.finished {
+            // errors can't be surfaced here, call finalize to handle them
+            let _ = self.finish();
+        }
+    }
+}
+
+// turns interleaved samples into SSND data, holding on to any codec state
+// between calls
+struct SoundEncoder {
+    spec: AiffSpec,
+    ima4: Option<ima4::Encoder>,
+}
+
+impl SoundEncoder {
+    fn new(spec: AiffSpec) -> SoundEncoder {
+        let ima4 = match &spec.compression_type {
+            ids::IMA4 => Some(ima4::Encoder::new(spec.num_channels as usize)),
+            _ => None,
+        };
+        SoundEncoder { spec, ima4 }
+    }
+
+    // samples are interleaved and hold whole frames. returns the number of
+    // frames to add to the COMM chunk
+    fn encode<'a, T: SampleType + 'a>(
+        &mut self,
+        samples: impl Iterator<Item = &'a T>,
+        out: &mut Vec<u8>,
+    ) -> u64 {
+        let spec = &self.spec;
+        let mut pcm =
+            Vec::with_capacity(samples.size_hint().0 * spec.bytes_per_point());
+        let mut sample_points = 0;
+        for sample in samples {
+            sample.encode(&mut pcm, spec.bit_rate);
+            sample_points += 1;
+        }
+
+        // compressed types are encoded from 16 bit linear PCM
+        let linear = pcm
+            .chunks_exact(2)
+            .map(|b| i16::from_be_bytes([b[0], b[1]]));
+        if let Some(encoder) = &mut self.ima4 {
+            return encoder.encode(linear, out) as u64;
+        }
+        match &spec.compression_type {
+            ids::ULAW => out.extend(linear.map(g711::linear_to_ulaw)),
+            ids::ALAW => out.extend(linear.map(g711::linear_to_alaw)),
+            _ => out.extend_from_slice(&pcm),
+        }
+
+        (sample_points / spec.num_channels as usize) as u64
+    }
+
+    // writes out anything held back by the codec
+    fn flush(&mut self, out: &mut Vec<u8>) -> u64 {
+        match &mut self.ima4 {
+            Some(encoder) => encoder.flush(out) as u64,
+            None => 0,
+        }
+    }
+}
+
+// FORM, FVER (AIFF-C), COMM and the SSND header up to the sound data,
+// including the offset padding
+fn write_header(
+    w: &mut impl Write,
+    spec: &AiffSpec,
+    num_sample_frames: u32,
+    sound_size: i32,
+    offset: u32,
+) -> io::Result<()> {
+    write_chunk_id(w, ids::FORM)?;
+    write_i32_be(w, spec.form_size(sound_size as u64) as i32)?;
+
+    if spec.is_aiff_c() {
+        write_chunk_id(w, ids::AIFF_C)?;
+        write_chunk_id(w, ids::FVER)?;
+        write_i32_be(w, FORMAT_VERSION_SIZE)?;
+        write_u32_be(w, AIFC_VERSION_1)?;
+    } else {
+        write_chunk_id(w, ids::AIFF)?;
+    }
+
+    write_chunk_id(w, ids::COMMON)?;
+    write_i32_be(w, spec.common_size())?;
+    write_i16_be(w, spec.num_channels)?;
+    write_u32_be(w, num_sample_frames)?;
+    write_i16_be(w, spec.bit_rate)?;
+    w.write_all(&encode_extended_precision_bytes(spec.sample_rate))?;
+    if spec.is_aiff_c() {
+        write_chunk_id(w, &spec.compression_type)?;
+        write_pstring(w, spec.compression_name())?;
+    }
+
+    write_chunk_id(w, ids::SOUND)?;
+    write_i32_be(w, sound_size)?;
+    write_u32_be(w, offset)?;
+    write_u32_be(w, spec.block_size)?;
+    io::copy(&mut io::repeat(0).take(offset as u64), w)?;
+    Ok(())
+}
+
+fn check_frames<T>(samples: &[T], spec: &AiffSpec) -> io::Result<()> {
+    if !samples.len().is_multiple_of(spec.num_channels as usize) {
+        return Err(invalid_input("samples do not fill the last frame"));
+    }
+    Ok(())
+}
+
+// compressed types are encoded from 16 bit samples, so bit_rate is the
+// width needed either way
+fn check_sample_type<T: SampleType>(spec: &AiffSpec) -> io::Result<()> {
+    if spec.bit_rate > T::MAX_BIT_WIDTH {
+        return Err(invalid_input("sample type too small for bit_rate"));
+    }
+    Ok(())
+}
+
+// planar channel buffers as interleaved sample points
+fn interleave<'a, T: 'a>(
+    channels: &'a [impl AsRef<[T]>],
+    spec: &AiffSpec,
+) -> io::Result<impl Iterator<Item = &'a T>> {
+    if channels.len() != spec.num_channels as usize {
+        return Err(invalid_input("expected a buffer for every channel"));
+    }
+    let frames = channels.first().map_or(0, |c| c.as_ref().len());
+    if channels.iter().any(|c| c.as_ref().len() != frames) {
+        return Err(invalid_input("channel buffers differ in length"));
+    }
+
+    Ok((0..frames).flat_map(move |frame| {
+        channels.iter().map(move |c| &c.as_ref()[frame])
+    }))
+}
+
+fn invalid_input(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+pub fn write_chunk_id(w: &mut impl Write, id: &ChunkID) -> io::Result<()> {
+    w.write_all(id)
+}
+
+pub fn write_i16_be(w: &mut impl Write, n: i16) -> io::Result<()> {
+    w.write_all(&n.to_be_bytes())
+}
+
+pub fn write_u32_be(w: &mut impl Write, n: u32) -> io::Result<()> {
+    w.write_all(&n.to_be_bytes())
+}
+
+pub fn write_i32_be(w: &mut impl Write, n: i32) -> io::Result<()> {
+    w.write_all(&n.to_be_bytes())
+}
+
+pub fn write_pstring(w: &mut impl Write, s: &[u8]) -> io::Result<()> {
+    let len = s.len().min(u8::MAX as usize);
+    w.write_all(&[len as u8])?;
+    w.write_all(&s[..len])?;
+    if len.is_multiple_of(2) {
+        // pad byte if the count byte + text is odd
+        w.write_all(&[0])?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::AiffReader;
+    use std::io::Cursor;
+
+    fn spec(num_channels: i16, bit_rate: i16) -> AiffSpec {
+        AiffSpec {
+            num_channels,
+            bit_rate,
+            sample_rate: 44100f64,
+            compression_type: *ids::NONE,
+            block_size: 0,
+        }
+    }
+
+    fn read(data: Vec<u8>) -> AiffReader<Cursor<Vec<u8>>> {
+        let mut reader = AiffReader::new(Cursor::new(data));
+        reader.read().unwrap();
+        assert!(reader.warnings().is_empty());
+        reader
+    }
+
+    #[test]
+    fn round_trips_pcm() {
+        for &bit_rate in &[8, 12, 16, 24, 32] {
+            let max = ((1i64 << (bit_rate - 1)) - 1) as i32;
+            let samples = vec![0, 1, -1, max, -max - 1, max / 3, -max / 7, 5];
+
+            let mut writer = AiffWriter::new(Vec::new(), spec(2, bit_rate));
+            writer.write(&samples).unwrap();
+            let reader = read(writer.into_inner());
+
+            let common = reader.form().as_ref().unwrap().common();
+            let common = common.as_ref().unwrap();
+            assert_eq!(common.bit_rate, bit_rate);
+            assert_eq!(common.num_sample_frames, 4);
+            assert_eq!(reader.samples::<i32>().unwrap(), samples);
+        }
+    }
+
+    #[test]
+    fn clips_out_of_range_samples() {
+        for &(bit_rate, max) in &[(8, 127), (12, 2047), (24, 8388607)] {
+            let samples = [1000, -1000, i32::MAX, i32::MIN];
+            let mut writer = AiffWriter::new(Vec::new(), spec(1, bit_rate));
+            writer.write(&samples).unwrap();
+            let reader = read(writer.into_inner());
+            let clipped = [1000.min(max), (-1000).max(-max - 1), max, -max - 1];
+            assert_eq!(reader.samples::<i32>().unwrap(), clipped);
+        }
+
+        // compressed types are encoded from 16 bit samples
+        let spec = AiffSpec {
+            compression_type: *ids::ULAW,
+            ..spec(1, 16)
+        };
+        let mut writer = AiffWriter::new(Vec::new(), spec);
+        writer.write(&[100_000i32, -100_000]).unwrap();
+        let reader = read(writer.into_inner());
+        let loudest = g711::ulaw_to_linear(g711::linear_to_ulaw(i16::MAX));
+        assert_eq!(reader.samples::<i16>().unwrap(), [loudest, -loudest]);
+    }
+
+    // a sink that refuses to seek once `fail` is set
+    struct FailingSeek {
+        data: Cursor<Vec<u8>>,
+        fail: bool,
+    }
+
+    impl Write for FailingSeek {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.data.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Seek for FailingSeek {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            match self.fail {
+                true => Err(io::Error::other("seek failed")),
+                false => self.data.seek(pos),
+            }
+        }
+    }
+
+    #[test]
+    fn failed_finalize_is_not_retried_on_drop() {
+        let mut sink = FailingSeek {
+            data: Cursor::new(Vec::new()),
+            fail: false,
+        };
+        let mut writer = AiffStreamWriter::new(&mut sink, spec(1, 24)).unwrap();
+        writer.write_frames(&[1i32]).unwrap();
+        let len = writer.sink_mut().unwrap().data.get_ref().len();
+
+        writer.sink_mut().unwrap().fail = true;
+        assert!(writer.finalize().is_err());
+        // just the pad byte, from the one attempt
+        assert_eq!(sink.data.get_ref().len(), len + 1);
+    }
+
+    #[test]
+    fn finalize_patches_sizes() {
+        // 7 mono 24 bit frames, so the SSND chunk needs a pad byte
+        let spec = spec(1, 24);
+        let samples: Vec<i32> = (0..7).map(|s| s * 1000 - 3000).collect();
+
+        let mut writer = AiffWriter::new(Vec::new(), spec);
+        writer.write(&samples).unwrap();
+        let expected = writer.into_inner();
+
+        let mut writer =
+            AiffStreamWriter::new(Cursor::new(Vec::new()), spec).unwrap();
+        writer.write_frames(&samples[..3]).unwrap();
+        writer.write_frames(&samples[3..]).unwrap();
+        assert_eq!(writer.num_sample_frames(), 7);
+        let sink = writer.finalize().unwrap();
+        assert_eq!(sink.position(), expected.len() as u64);
+
+        let data = sink.into_inner();
+        assert_eq!(data, expected);
+        let reader = read(data);
+        let form = reader.form().as_ref().unwrap();
+        assert_eq!(form.size() as usize, expected.len() - 8);
+        assert_eq!(reader.samples::<i32>().unwrap(), samples);
+    }
+}